@@ -0,0 +1,451 @@
+use std::{
+	collections::HashMap,
+	fmt::Display,
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::{Arc, LazyLock, RwLock},
+	time::Duration,
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::{hyprctl::Color, log::Level};
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct Arguments {
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum Command {
+	///Parse and validate the config file without starting anything.
+	Check {
+		///Defaults to `$XDG_CONFIG_HOME/homehelper/config.toml`
+		path: Option<PathBuf>,
+	},
+}
+
+pub fn launch(arguments: Arguments) -> Result<()> {
+	match arguments.command {
+		Command::Check { path } => {
+			let path = match path {
+				Some(path) => path,
+				None => default_path()?,
+			};
+			if !path.exists() {
+				println!("{} does not exist; the defaults will be used.", path.display());
+				return Ok(());
+			}
+			Config::load(&path)?;
+			println!("{} is valid.", path.display());
+		}
+	}
+	Ok(())
+}
+
+static CURRENT: LazyLock<RwLock<Arc<Config>>> = LazyLock::new(|| RwLock::new(Arc::new(Config::default())));
+
+///The configuration the daemon is currently running with.
+pub fn current() -> Arc<Config> {
+	Arc::clone(&CURRENT.read().unwrap())
+}
+
+pub fn set(config: Config) {
+	*CURRENT.write().unwrap() = Arc::new(config);
+}
+
+///`$XDG_CONFIG_HOME`, or its default.
+pub fn config_home() -> Result<PathBuf> {
+	Ok(match std::env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(std::env::var_os("HOME").context("Neither XDG_CONFIG_HOME nor HOME is set")?).join(".config"),
+	})
+}
+
+pub fn default_path() -> Result<PathBuf> {
+	Ok(config_home()?.join("homehelper").join("config.toml"))
+}
+
+///Deserializes any `FromStr` type from a string, so its parse error points at the offending key.
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+	D: Deserializer<'de>,
+	T: FromStr,
+	T::Err: Display,
+{
+	let s = String::deserialize(deserializer)?;
+	s.parse().map_err(serde::de::Error::custom)
+}
+
+fn some_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: FromStr,
+	T::Err: Display,
+{
+	from_str(deserializer).map(Some)
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub daemon: DaemonConfig,
+	pub submap: SubmapConfig,
+	pub eww: EwwConfig,
+	pub active_window: ActiveWindowConfig,
+	pub waybar: WaybarConfig,
+	pub layout: LayoutConfig,
+	pub notifications: NotificationConfig,
+	pub icons: IconConfig,
+	pub logging: LogConfig,
+	pub hooks: Vec<HookConfig>,
+	pub scripting: ScriptingConfig,
+	pub dbus: DbusConfig,
+} impl Config {
+	pub fn load(path: &Path) -> Result<Self> {
+		let text = std::fs::read_to_string(path)
+			.with_context(|| format!("Could not read {}", path.display()))?;
+		let config: Config = toml::from_str(&text)
+			.with_context(|| format!("Invalid config in {}", path.display()))?;
+		if config.daemon.deprecated_tick.is_some() {
+			crate::warn!("`daemon.tick_ms` in {} is deprecated and ignored", path.display());
+		}
+		Ok(config)
+	}
+
+	///Loads `path`, or the default path, falling back to the defaults if it doesn't exist.
+	pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+		let path = match path {
+			Some(path) => return Self::load(path),
+			None => default_path()?,
+		};
+		if path.exists() {
+			Self::load(&path)
+		} else {
+			Ok(Config::default())
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+	///How long a failed step waits before its first restart. Doubles with each failure in a row.
+	pub restart_backoff_ms: u64,
+	pub max_restart_backoff_ms: u64,
+	///Ignored since the daemon sleeps until something happens; only accepted so that old configs
+	///still load.
+	#[serde(rename = "tick_ms")]
+	pub deprecated_tick: Option<u64>,
+} impl DaemonConfig {
+	pub fn restart_backoff(&self) -> Duration {
+		Duration::from_millis(self.restart_backoff_ms)
+	}
+	pub fn max_restart_backoff(&self) -> Duration {
+		Duration::from_millis(self.max_restart_backoff_ms)
+	}
+} impl Default for DaemonConfig {
+	fn default() -> Self {
+		DaemonConfig {
+			restart_backoff_ms: 1000,
+			max_restart_backoff_ms: 60_000,
+			deprecated_tick: None,
+		}
+	}
+}
+
+///The kitty panel shown while a submap is active.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubmapConfig {
+	pub enabled: bool,
+	pub edge: String,
+	pub layer: String,
+	pub app_id: String,
+	///Passed to `kitty +kitten panel` before the command, e.g. `["--margin-top", "40"]`.
+	pub extra_args: Vec<String>,
+	///How long a submap must stay active before the panel opens.
+	pub delay_ms: u64,
+} impl SubmapConfig {
+	pub fn delay(&self) -> Duration {
+		Duration::from_millis(self.delay_ms)
+	}
+} impl Default for SubmapConfig {
+	fn default() -> Self {
+		SubmapConfig {
+			enabled: true,
+			edge: String::from("center-sized"),
+			layer: String::from("top"),
+			app_id: String::from("homehelper-submap"),
+			extra_args: vec![],
+			delay_ms: 0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EwwConfig {
+	///Whether special workspaces are sent to `listen-eww` at all.
+	pub include_special: bool,
+} impl Default for EwwConfig {
+	fn default() -> Self {
+		EwwConfig {
+			include_special: true,
+		}
+	}
+}
+
+///How `listen-active-window` shows titles.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActiveWindowConfig {
+	///Longer titles are cut to this many characters, `ellipsis` included. 0 keeps them whole.
+	pub max_title_length: usize,
+	pub ellipsis: String,
+	///Applied in order, before the title is cut.
+	pub rewrite: Vec<TitleRewrite>,
+} impl ActiveWindowConfig {
+	pub fn title(&self, class: &str, title: &str) -> String {
+		let mut title = title.to_string();
+		for rule in &self.rewrite {
+			if rule.class.as_ref().is_none_or(|pattern| pattern.is_match(class)) {
+				title = rule.title.replace_all(&title, rule.replace.as_str()).into_owned();
+			}
+		}
+
+		if self.max_title_length == 0 || title.chars().count() <= self.max_title_length {
+			return title;
+		}
+		let keep = self.max_title_length.saturating_sub(self.ellipsis.chars().count());
+		let mut cut: String = title.chars().take(keep).collect();
+		cut.truncate(cut.trim_end().len());
+		cut.push_str(&self.ellipsis);
+		cut
+	}
+} impl Default for ActiveWindowConfig {
+	fn default() -> Self {
+		ActiveWindowConfig {
+			max_title_length: 0,
+			ellipsis: String::from("…"),
+			rewrite: vec![],
+		}
+	}
+}
+
+///How `--format waybar` renders the workspace list. `{label}` is replaced with the workspace's
+///index, icon and name, escaped for Pango.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaybarConfig {
+	pub workspace: String,
+	///The workspace shown on the focused monitor.
+	pub active_workspace: String,
+	pub separator: String,
+} impl Default for WaybarConfig {
+	fn default() -> Self {
+		WaybarConfig {
+			workspace: String::from("{label}"),
+			active_workspace: String::from("<b>{label}</b>"),
+			separator: String::from(" "),
+		}
+	}
+}
+
+///Short codes `listen-layout` sends alongside layout names.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+	///Layout name, like `English (US)`, to its code, like `us`.
+	pub codes: HashMap<String, String>,
+} impl LayoutConfig {
+	///The name itself for layouts missing from `codes`.
+	pub fn code(&self, layout: &str) -> String {
+		self.codes.get(layout).cloned().unwrap_or_else(|| layout.to_string())
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TitleRewrite {
+	///Only titles of windows whose class matches. Every window if unset.
+	#[serde(default, deserialize_with = "some_from_str")]
+	pub class: Option<Regex>,
+	///Every match in the title is replaced.
+	#[serde(deserialize_with = "from_str")]
+	pub title: Regex,
+	///May refer to groups in `title`, like `$1`.
+	#[serde(default)]
+	pub replace: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+	///Whether errors are also shown as Hyprland notifications.
+	pub enabled: bool,
+	pub color: Color,
+	pub duration_ms: u64,
+	///An error identical to one shown this recently is only logged.
+	pub dedup_window_ms: u64,
+	pub max_per_minute: usize,
+} impl NotificationConfig {
+	pub fn duration(&self) -> Duration {
+		Duration::from_millis(self.duration_ms)
+	}
+} impl Default for NotificationConfig {
+	fn default() -> Self {
+		NotificationConfig {
+			enabled: true,
+			color: Color::Rgb(255, 0, 0),
+			duration_ms: 5000,
+			dedup_window_ms: 30_000,
+			max_per_minute: 5,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconConfig {
+	///Special workspace name (without `special:`) to icon. Replaces the built-in table when set.
+	pub special: HashMap<String, String>,
+	///Icon for special workspaces missing from `special`. Their name is shown if unset.
+	pub special_fallback: Option<String>,
+	///Window class to icon, for the windows on each workspace.
+	pub classes: HashMap<String, String>,
+	///Tried in order for classes missing from `classes`.
+	pub class_patterns: Vec<ClassIcon>,
+	///Icon for windows matching neither. They get none if unset.
+	pub class_fallback: Option<String>,
+} impl IconConfig {
+	pub fn special_icon(&self, name: &str) -> String {
+		let short_name = name.strip_prefix("special:").unwrap_or(name);
+		self.special.get(short_name)
+			.or(self.special_fallback.as_ref())
+			.cloned()
+			.unwrap_or_else(|| name.to_string())
+	}
+
+	pub fn class_icon(&self, class: &str) -> Option<&str> {
+		self.classes.get(class)
+			.or_else(|| self.class_patterns.iter().find(|p| p.class.is_match(class)).map(|p| &p.icon))
+			.or(self.class_fallback.as_ref())
+			.map(String::as_str)
+	}
+} impl Default for IconConfig {
+	fn default() -> Self {
+		IconConfig {
+			special: [
+				("guide", "󰈹"),
+				("term", ""),
+				("other", ""),
+				("music", ""),
+				("notes", ""),
+				("testing", ""),
+			].into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+			special_fallback: None,
+			classes: HashMap::new(),
+			class_patterns: vec![],
+			class_fallback: None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClassIcon {
+	#[serde(deserialize_with = "from_str")]
+	pub class: Regex,
+	pub icon: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+	///Records below this level are dropped entirely.
+	pub level: Level,
+	///How many records `homehelper remote logs` can look back on.
+	pub buffer: usize,
+} impl Default for LogConfig {
+	fn default() -> Self {
+		LogConfig {
+			level: Level::Info,
+			buffer: 1000,
+		}
+	}
+}
+
+///A shell command run when a Hyprland event happens, from `[[hooks]]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+	///Event name as Hyprland sends it, e.g. `openwindow`, or `*` for every event.
+	pub event: String,
+	///Run with `sh -c`. The event's fields are passed as `HH_*` environment variables.
+	pub command: String,
+	///Only run if the event's window class matches. Events without the field never match.
+	#[serde(default, deserialize_with = "some_from_str")]
+	pub class: Option<Regex>,
+	#[serde(default, deserialize_with = "some_from_str")]
+	pub title: Option<Regex>,
+	///Matched against the workspace name, or its id if the event only has that.
+	#[serde(default, deserialize_with = "some_from_str")]
+	pub workspace: Option<Regex>,
+	#[serde(default, deserialize_with = "some_from_str")]
+	pub monitor: Option<Regex>,
+	///Events arriving while this many copies are running are skipped.
+	#[serde(default = "HookConfig::default_max_concurrent")]
+	pub max_concurrent: usize,
+	///The command is killed after this long.
+	#[serde(default = "HookConfig::default_timeout_ms")]
+	pub timeout_ms: u64,
+} impl HookConfig {
+	fn default_max_concurrent() -> usize {
+		1
+	}
+	fn default_timeout_ms() -> u64 {
+		30_000
+	}
+
+	pub fn timeout(&self) -> Duration {
+		Duration::from_millis(self.timeout_ms)
+	}
+}
+
+///Rhai scripts, if homehelper was built with the `scripting` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptingConfig {
+	pub enabled: bool,
+	///Every `*.rhai` file in here is loaded. Defaults to `$XDG_CONFIG_HOME/homehelper/scripts`.
+	pub dir: Option<PathBuf>,
+	///A script running longer than this, e.g. stuck in a loop, is stopped with an error.
+	pub max_operations: u64,
+} impl ScriptingConfig {
+	#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+	pub fn dir(&self) -> Result<PathBuf> {
+		match &self.dir {
+			Some(dir) => Ok(dir.clone()),
+			None => Ok(config_home()?.join("homehelper").join("scripts")),
+		}
+	}
+} impl Default for ScriptingConfig {
+	fn default() -> Self {
+		ScriptingConfig {
+			enabled: true,
+			dir: None,
+			max_operations: 1_000_000,
+		}
+	}
+}
+
+///The `org.homehelper.Daemon` service, if homehelper was built with the `dbus` feature.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbusConfig {
+	pub enabled: bool,
+}
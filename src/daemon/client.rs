@@ -0,0 +1,434 @@
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	io::{self, Read, Write},
+	os::{fd::{AsRawFd, RawFd}, unix::net::UnixStream},
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{reply::{RemoteError, Reply}, TimerId};
+
+///Clients that stop reading are dropped once this much output is waiting for them.
+const MAX_QUEUED_BYTES: usize = 1 << 20;
+///Clients that send more than this without completing a frame are dropped.
+const MAX_FRAME_BYTES: usize = 1 << 16;
+///Clients that haven't sent their command this long after connecting are dropped.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+///How frames are encoded on a connection. Decided by the first byte the remote sends: anything
+///printable is taken as JSON, since a CBOR handshake is a map, which never starts with one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+	///What `homehelper remote` speaks.
+	Cbor,
+	///One JSON value per line, so scripts can talk to the daemon with `socat`. The handshake is
+	///optional.
+	JsonLines,
+}
+
+#[derive(Debug)]
+struct ClientShared {
+	id: u64,
+	outgoing: VecDeque<u8>,
+	closed: bool,
+	connected: Instant,
+	///Name of the command it sent, once it has.
+	command: Option<&'static str>,
+	framing: Framing,
+}
+
+///Handle to one connected remote, given to command handlers and kept by streaming steps.
+///Writes are queued and flushed by the main loop, so sending never blocks.
+#[derive(Debug, Clone)]
+pub struct Client(Rc<RefCell<ClientShared>>);
+impl Client {
+	pub fn id(&self) -> u64 {
+		self.0.borrow().id
+	}
+
+	pub fn command(&self) -> Option<&'static str> {
+		self.0.borrow().command
+	}
+
+	pub fn set_command(&self, command: &'static str) {
+		self.0.borrow_mut().command = Some(command);
+	}
+
+	pub fn connected_for(&self) -> Duration {
+		self.0.borrow().connected.elapsed()
+	}
+
+	pub fn queued_bytes(&self) -> usize {
+		self.0.borrow().outgoing.len()
+	}
+
+	///Queues one raw frame. Fails with `BrokenPipe` once the client is gone, so streaming steps
+	///know to stop.
+	pub fn send_frame<T: Serialize + ?Sized>(&self, data: &T) -> Result<()> {
+		let mut shared = self.0.borrow_mut();
+		if shared.closed {
+			return Err(io::Error::from(io::ErrorKind::BrokenPipe).into());
+		}
+
+		let mut frame = vec![];
+		match shared.framing {
+			Framing::Cbor => ciborium::into_writer(data, &mut frame)?,
+			Framing::JsonLines => {
+				serde_json::to_writer(&mut frame, data)?;
+				frame.push(b'\n');
+			},
+		}
+		if shared.outgoing.len() + frame.len() > MAX_QUEUED_BYTES {
+			shared.closed = true;
+			shared.outgoing.clear();
+			return Err(io::Error::new(
+				io::ErrorKind::BrokenPipe,
+				format!("Dropped client {}: more than {MAX_QUEUED_BYTES} bytes of unread output", shared.id),
+			).into());
+		}
+		shared.outgoing.extend(frame);
+		Ok(())
+	}
+
+	///Queues a successful [`Reply`].
+	pub fn send<T: Serialize + ?Sized>(&self, data: &T) -> Result<()> {
+		self.send_frame(&Reply::Ok(data))
+	}
+
+	///Queues a failed [`Reply`].
+	pub fn send_error(&self, error: &anyhow::Error) -> Result<()> {
+		self.send_frame(&Reply::<()>::Err(RemoteError::from(error)))
+	}
+}
+
+///Reads `"Status"` and `{"Status": null}` like `{"Status": {}}`, so JSON remotes can leave out the
+///body of commands that have no options or defaults for all of them.
+fn json_command(frame: serde_json::Value) -> serde_json::Result<super::commands::Command> {
+	use serde_json::{json, Value};
+	let name = match &frame {
+		Value::String(name) => name.clone(),
+		Value::Object(map) if map.len() == 1 && map.values().all(Value::is_null) => map.keys().next().cloned().unwrap_or_default(),
+		_ => return serde_json::from_value(frame),
+	};
+	serde_json::from_value(json!({ &name: null }))
+		.or_else(|_| serde_json::from_value(json!({ &name: {} })))
+		//The error for what was actually sent is the most telling.
+		.or_else(|_| serde_json::from_value(frame))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+	AwaitingHandshake,
+	AwaitingCommand,
+	Dispatched,
+}
+
+///What the main loop should do with a frame read from a [`Connection`].
+pub enum Incoming {
+	Handshake(super::handshake::Handshake),
+	Command(super::commands::Command),
+}
+
+///The daemon's side of a [`Client`]: owns the socket and does the actual I/O.
+#[derive(Debug)]
+pub struct Connection {
+	stream: UnixStream,
+	incoming: Vec<u8>,
+	///The remote shut down its side for writing; it may still be reading.
+	eof: bool,
+	///Unset until the remote has sent something.
+	framing: Option<Framing>,
+	state: ConnectionState,
+	client: Client,
+	///Fires [`REQUEST_TIMEOUT`] after connecting.
+	deadline: Option<TimerId>,
+}
+impl AsRawFd for Connection {
+	fn as_raw_fd(&self) -> RawFd {
+		self.stream.as_raw_fd()
+	}
+}
+impl Connection {
+	pub fn new(stream: UnixStream, id: u64) -> Result<Self> {
+		stream.set_nonblocking(true)?;
+		Ok(Connection {
+			stream,
+			incoming: vec![],
+			eof: false,
+			framing: None,
+			state: ConnectionState::AwaitingHandshake,
+			deadline: None,
+			client: Client(Rc::new(RefCell::new(ClientShared {
+				id,
+				outgoing: VecDeque::new(),
+				closed: false,
+				connected: Instant::now(),
+				command: None,
+				framing: Framing::Cbor,
+			}))),
+		})
+	}
+
+	pub fn client(&self) -> &Client {
+		&self.client
+	}
+
+	pub fn framing(&self) -> Option<Framing> {
+		self.framing
+	}
+
+	pub fn set_deadline(&mut self, timer: TimerId) {
+		self.deadline = Some(timer);
+	}
+
+	pub fn deadline(&self) -> Option<TimerId> {
+		self.deadline
+	}
+
+	///The deadline passed: drops the connection unless its command has arrived. Returns whether it
+	///was dropped.
+	pub fn time_out(&mut self) -> bool {
+		if self.state == ConnectionState::Dispatched {
+			return false;
+		}
+		self.client.0.borrow_mut().closed = true;
+		true
+	}
+
+	///What to wait for in `poll(2)`: input until the remote stops sending, output while any is
+	///queued.
+	pub fn poll_events(&self) -> libc::c_short {
+		let mut events = 0;
+		if !self.eof {
+			events |= libc::POLLIN;
+		}
+		if !self.client.0.borrow().outgoing.is_empty() {
+			events |= libc::POLLOUT;
+		}
+		events
+	}
+
+	///`poll(2)` reported that the remote is completely gone. Anything it sent before that is
+	///still read, but a handled connection can't deliver anything more.
+	pub fn hang_up(&mut self) {
+		if self.state == ConnectionState::Dispatched {
+			self.client.0.borrow_mut().closed = true;
+		}
+	}
+
+	///Reads everything the remote has sent so far.
+	pub fn fill(&mut self) -> Result<()> {
+		let mut buf = [0; 4096];
+		while !self.eof {
+			match self.stream.read(&mut buf) {
+				Ok(0) => self.eof = true,
+				//Nothing is expected after the command; only watch for the hang-up.
+				Ok(_) if self.state == ConnectionState::Dispatched => {},
+				Ok(n) => {
+					self.incoming.extend_from_slice(&buf[..n]);
+					if self.incoming.len() > MAX_FRAME_BYTES {
+						self.client.0.borrow_mut().closed = true;
+						anyhow::bail!("Dropped client {}: frame larger than {MAX_FRAME_BYTES} bytes", self.client.id());
+					}
+				},
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+				Err(e) => {
+					self.client.0.borrow_mut().closed = true;
+					return Err(e.into());
+				},
+			}
+		}
+		Ok(())
+	}
+
+	///Decodes the next complete frame, if one has arrived.
+	pub fn next_frame(&mut self) -> Result<Option<Incoming>> {
+		if self.framing.is_none() && let Some(&first) = self.incoming.first() {
+			let framing = if first.is_ascii_graphic() || first.is_ascii_whitespace() { Framing::JsonLines } else { Framing::Cbor };
+			self.framing = Some(framing);
+			self.client.0.borrow_mut().framing = framing;
+		}
+
+		let frame = match self.state {
+			//JSON remotes may skip straight to the command.
+			ConnectionState::AwaitingHandshake if self.framing == Some(Framing::JsonLines) => {
+				self.decode::<serde_json::Value>()?.map(|frame| {
+					if frame.get("protocol").is_some() {
+						self.state = ConnectionState::AwaitingCommand;
+						serde_json::from_value(frame).map(Incoming::Handshake)
+					} else {
+						self.state = ConnectionState::Dispatched;
+						json_command(frame).map(Incoming::Command)
+					}
+				}).transpose()?
+			},
+			ConnectionState::AwaitingHandshake => self.decode()?.map(|h| {
+				self.state = ConnectionState::AwaitingCommand;
+				Incoming::Handshake(h)
+			}),
+			ConnectionState::AwaitingCommand => {
+				let command = match self.framing {
+					Some(Framing::JsonLines) => self.decode::<serde_json::Value>()?.map(json_command).transpose()?,
+					_ => self.decode()?,
+				};
+				command.map(|c| {
+					self.state = ConnectionState::Dispatched;
+					Incoming::Command(c)
+				})
+			},
+			ConnectionState::Dispatched => None,
+		};
+		if frame.is_none() && self.eof && self.state != ConnectionState::Dispatched {
+			//Hung up before sending a whole request.
+			self.client.0.borrow_mut().closed = true;
+		}
+		Ok(frame)
+	}
+
+	fn decode<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+		match self.framing {
+			None => Ok(None),
+			Some(Framing::Cbor) => self.decode_cbor(),
+			Some(Framing::JsonLines) => self.decode_json(),
+		}
+	}
+
+	fn decode_cbor<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+		let mut reader = &self.incoming[..];
+		match ciborium::from_reader(&mut reader) {
+			Ok(frame) => {
+				let used = self.incoming.len() - reader.len();
+				self.incoming.drain(..used);
+				Ok(Some(frame))
+			},
+			Err(ciborium::de::Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	///Skips blank lines. A last line without a newline counts once the remote stops sending.
+	fn decode_json<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+		loop {
+			let line: Vec<u8> = match self.incoming.iter().position(|b| *b == b'\n') {
+				Some(end) => self.incoming.drain(..=end).collect(),
+				None if self.eof => self.incoming.drain(..).collect(),
+				None => return Ok(None),
+			};
+			if line.trim_ascii().is_empty() {
+				if self.incoming.is_empty() {
+					return Ok(None);
+				}
+				continue;
+			}
+			return Ok(Some(serde_json::from_slice(&line)?));
+		}
+	}
+
+	///Stops reading frames; the connection closes once its output is flushed.
+	pub fn finish(&mut self) {
+		self.state = ConnectionState::Dispatched;
+	}
+
+	///Writes as much queued output as the socket will take.
+	pub fn flush(&mut self) -> Result<()> {
+		let mut shared = self.client.0.borrow_mut();
+		while !shared.outgoing.is_empty() {
+			let (front, _) = shared.outgoing.as_slices();
+			match self.stream.write(front) {
+				Ok(n) => {
+					shared.outgoing.drain(..n);
+				},
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+				Err(e) => {
+					shared.closed = true;
+					shared.outgoing.clear();
+					return Err(e.into());
+				},
+			}
+		}
+		Ok(())
+	}
+
+	///Whether the connection has nothing left to do and can be closed.
+	pub fn is_finished(&self) -> bool {
+		let shared = self.client.0.borrow();
+		if shared.closed {
+			return true;
+		}
+		//Once the command has been handled, the connection lives as long as something (usually a
+		//streaming step) still holds its `Client`, plus however long it takes to flush.
+		self.state == ConnectionState::Dispatched
+			&& shared.outgoing.is_empty()
+			&& Rc::strong_count(&self.client.0) == 1
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///A connection that has read `input`, with the remote's end.
+	fn connection(input: &[u8]) -> (Connection, UnixStream) {
+		let (daemon, mut remote) = UnixStream::pair().unwrap();
+		remote.write_all(input).unwrap();
+		let mut connection = Connection::new(daemon, 1).unwrap();
+		connection.fill().unwrap();
+		(connection, remote)
+	}
+
+	#[test]
+	fn printable_first_bytes_mean_json() {
+		for input in [&b"Workspaces\n"[..], b"\"Statsu\"\n", b"[]\n", b" {\"Status\":1}\n"] {
+			let (mut connection, _remote) = connection(input);
+			assert!(connection.next_frame().is_err(), "{}", String::from_utf8_lossy(input));
+			assert_eq!(connection.framing, Some(Framing::JsonLines));
+			//So the error can still be sent back.
+			assert!(!connection.client.0.borrow().closed);
+		}
+	}
+
+	#[test]
+	fn json_commands_may_leave_out_defaults() {
+		for (input, command) in [
+			("\"Status\"", "Status"),
+			("{\"Status\":null}", "Status"),
+			("{\"Status\":{}}", "Status"),
+			("{\"Logs\":{\"follow\":true}}", "Logs"),
+			("\"Workspaces\"", "Workspaces"),
+			("{\"Workspaces\":null}", "Workspaces"),
+		] {
+			let (mut connection, _remote) = connection(format!("{input}\n").as_bytes());
+			match connection.next_frame() {
+				Ok(Some(Incoming::Command(c))) => assert_eq!(c.capability(), command, "{input}"),
+				Ok(_) => panic!("{input}: no command"),
+				Err(e) => panic!("{input}: {e:#}"),
+			}
+		}
+	}
+
+	#[test]
+	fn bad_cbor_is_answered() {
+		//A map with a reserved length.
+		let (mut connection, _remote) = connection(&[0xbc]);
+		assert!(connection.next_frame().is_err());
+		assert_eq!(connection.framing(), Some(Framing::Cbor));
+		assert!(connection.client().send_error(&anyhow::anyhow!("bad")).is_ok());
+	}
+
+	#[test]
+	fn only_connections_without_a_command_time_out() {
+		let (mut silent, _remote) = connection(b"");
+		assert!(silent.time_out());
+		assert!(silent.is_finished());
+
+		let (mut handled, _remote) = connection(b"\"Status\"\n");
+		assert!(handled.next_frame().unwrap().is_some());
+		assert!(!handled.time_out());
+	}
+}
@@ -0,0 +1,171 @@
+use super::prelude::*;
+use std::time::Duration;
+use crate::{daemon::status::DaemonStatus, log::{self, Level, Record}};
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Reload;
+impl HandleDaemon for Reload {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		d.reload()?;
+		send!(s, ())?;
+		Ok(())
+	}
+}
+impl HandleRemote for Reload {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		println!("Reloaded");
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Quit;
+impl HandleDaemon for Quit {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		d.request_exit();
+		send!(s, ())?;
+		Ok(())
+	}
+}
+impl HandleRemote for Quit {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Logs {
+	///Keep printing new records as they are written.
+	#[arg(short, long)]
+	#[serde(default)]
+	follow: bool,
+	///Hide records below this level.
+	#[arg(short, long, value_enum, default_value_t = Level::Info)]
+	#[serde(default)]
+	level: Level,
+	///Print one JSON object per record.
+	#[arg(long)]
+	#[serde(default)]
+	json: bool,
+}
+impl HandleDaemon for Logs {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		let records = log::records(self.level, None);
+		let last_seq = records.last().map(|r| r.seq);
+		send!(s, records)?;
+		if self.follow {
+			d.add_step(LogsStep { socket: s, level: self.level, last_seq });
+		}
+		Ok(())
+	}
+}
+impl HandleRemote for Logs {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		loop {
+			let records: Vec<Record> = recv!(s)?;
+			for record in records {
+				if self.json {
+					println!("{}", serde_json::to_string(&record)?);
+				} else {
+					println!("{record}");
+				}
+			}
+			if !self.follow {
+				return Ok(());
+			}
+		}
+	}
+}
+
+#[derive(Debug)]
+struct LogsStep {
+	socket: Client,
+	level: Level,
+	last_seq: Option<u64>,
+} impl MainLoopStep for LogsStep {
+	fn name(&self) -> &'static str {
+		"logs"
+	}
+	fn step(&mut self, _: &mut Context) -> Result<StepState> {
+		let records = log::records(self.level, self.last_seq);
+		let Some(last) = records.last() else {
+			return Ok(StepState::KeepActive);
+		};
+		self.last_seq = Some(last.seq);
+		match send!(self.socket, records) {
+			Ok(()) => Ok(StepState::KeepActive),
+			Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) => Ok(StepState::Done),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Status {
+	///Print the status as JSON instead.
+	#[arg(long)]
+	#[serde(default)]
+	json: bool,
+}
+impl HandleDaemon for Status {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		let status = d.status(&s);
+		send!(s, status)?;
+		Ok(())
+	}
+}
+impl HandleRemote for Status {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let status: DaemonStatus = recv!(s)?;
+		if self.json {
+			println!("{}", serde_json::to_string_pretty(&status)?);
+			return Ok(());
+		}
+
+		let secs = |ms: u64| Duration::from_millis(ms).as_secs_f32();
+		println!("homehelper {} (pid {}), up {:.0}s", status.version, status.pid, secs(status.uptime_ms));
+		let hyprland = status.hyprland;
+		print!("Hyprland {}", hyprland.version.as_deref().unwrap_or("(unknown version)"));
+		if let Some(instance) = hyprland.instance {
+			print!(", instance {instance}");
+		}
+		println!();
+		if let Some(error) = hyprland.error {
+			println!("  {error}");
+		}
+
+		println!("Clients:");
+		for client in status.clients {
+			println!("  #{} {}: connected {:.1}s, {} bytes queued", client.id, client.command.as_deref().unwrap_or("(handshake)"), secs(client.connected_ms), client.queued_bytes);
+		}
+
+		println!("Steps:");
+		for step in status.steps {
+			print!("  {} ({:?}): {} events, {} failures, {} restarts", step.name, step.supervision, step.events, step.failures, step.restarts);
+			if let Some(ms) = step.restarting_in_ms {
+				print!(", restarting in {:.1}s", secs(ms));
+			}
+			println!();
+			if let Some(error) = step.last_error {
+				println!("    last error: {error}");
+			}
+		}
+
+		println!("Socket1 requests:");
+		for call in status.socket1 {
+			print!("  {}: {} calls, mean {}us, max {}us", call.request, call.count, call.mean_us, call.max_us);
+			if call.errors > 0 {
+				print!(", {} errors", call.errors);
+			}
+			println!();
+		}
+
+		println!("Events:");
+		for event in status.events {
+			println!("  {}: {} total, {} in the last minute, {:.2}/s", event.kind, event.count, event.last_minute, event.per_second);
+		}
+		Ok(())
+	}
+}
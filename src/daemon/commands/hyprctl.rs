@@ -1,35 +1,146 @@
 use super::prelude::*;
+use crate::hyprctl::WindowAddress;
 
-
-
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
 pub struct Workspaces;
 impl HandleDaemon for Workspaces {
-	fn daemon(_: &mut Daemon, mut s: UnixStream) -> Result<()> {
+	fn daemon(self, _: &mut Daemon, s: Client) -> Result<()> {
 		send!(s, hyprctl::workspaces()?)?;
 		Ok(())
 	}
 }
 impl HandleRemote for Workspaces {
-	fn remote(mut s: std::os::unix::net::UnixStream) -> Result<()> {
+	fn remote(self, mut s: std::os::unix::net::UnixStream) -> Result<()> {
 		let workspaces: Vec<hyprctl::Workspace> = recv!(s)?;
 		println!("{}", serde_json::to_string(&workspaces)?);
 		Ok(())
 	}
 }
 
-
-
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
 pub struct Monitors;
 impl HandleDaemon for Monitors {
-	fn daemon(_: &mut Daemon, mut s: UnixStream) -> Result<()> {
+	fn daemon(self, _: &mut Daemon, s: Client) -> Result<()> {
 	    send!(s, hyprctl::monitors()?)?;
 		Ok(())
 	}
 }
 impl HandleRemote for Monitors {
-	fn remote(mut s: UnixStream) -> Result<()> {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
 	    let monitors: Vec<hyprctl::Monitor> = recv!(s)?;
 		println!("{}", serde_json::to_string(&monitors)?);
 		Ok(())
 	}
 }
+
+fn default_duration() -> u64 {
+	5000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Notify {
+	#[arg(short, long, value_enum, default_value_t = hyprctl::NotifyIcon::Info)]
+	#[serde(default)]
+	icon: hyprctl::NotifyIcon,
+	///How long to show it for, in milliseconds.
+	#[arg(short, long, default_value_t = default_duration())]
+	#[serde(default = "default_duration")]
+	duration: u64,
+	///`#rrggbb`, `#rrggbbaa`, `rgb(r,g,b)`, `rgba(r,g,b,a)` (alpha from 0 to 255), `rgb(rrggbb)` or
+	///`rgba(rrggbbaa)`. Defaults to one matching the icon.
+	#[arg(short, long)]
+	color: Option<hyprctl::Color>,
+	#[arg(short, long)]
+	font_size: Option<u32>,
+	#[arg(required = true)]
+	message: Vec<String>,
+}
+impl HandleDaemon for Notify {
+	fn daemon(self, _: &mut Daemon, s: Client) -> Result<()> {
+		hyprctl::send_notification(&hyprctl::Notification {
+			icon: self.icon,
+			time: std::time::Duration::from_millis(self.duration),
+			color: self.color,
+			font_size: self.font_size,
+			message: self.message.join(" "),
+		})?;
+		send!(s, ())?;
+		Ok(())
+	}
+}
+impl HandleRemote for Notify {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct DismissNotify {
+	///How many to dismiss, oldest first. All of them if unset.
+	#[arg(short, long)]
+	amount: Option<u32>,
+}
+impl HandleDaemon for DismissNotify {
+	fn daemon(self, _: &mut Daemon, s: Client) -> Result<()> {
+		hyprctl::dismiss_notify(self.amount)?;
+		send!(s, ())?;
+		Ok(())
+	}
+}
+impl HandleRemote for DismissNotify {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		Ok(())
+	}
+}
+
+///The urgent window to focus after `last`. Focusing a window clears it, so this is usually the
+///oldest; if that didn't work, the next.
+fn next_urgent(urgent: &[WindowAddress], last: Option<WindowAddress>) -> Option<WindowAddress> {
+	let after = last
+		.and_then(|last| urgent.iter().position(|address| *address == last))
+		.map_or(0, |i| i + 1);
+	urgent.get(after).or(urgent.first()).copied()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct FocusUrgent;
+impl HandleDaemon for FocusUrgent {
+	//Does nothing if no window wants attention, so it can be bound to a key.
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		if let Some(address) = next_urgent(d.state.urgent(), d.last_urgent_focus) {
+			hyprctl::dispatch("focuswindow", &format!("address:0x{address:x}"))?;
+			d.last_urgent_focus = Some(address);
+		}
+		send!(s, ())?;
+		Ok(())
+	}
+}
+impl HandleRemote for FocusUrgent {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn next_urgent_cycles() {
+		let urgent = [0xa, 0xb, 0xc];
+		assert_eq!(next_urgent(&urgent, None), Some(0xa));
+		assert_eq!(next_urgent(&urgent, Some(0xa)), Some(0xb));
+		assert_eq!(next_urgent(&urgent, Some(0xc)), Some(0xa));
+		//No longer urgent, like after it was focused.
+		assert_eq!(next_urgent(&urgent, Some(0xd)), Some(0xa));
+	}
+
+	#[test]
+	fn next_urgent_without_urgent_windows() {
+		assert_eq!(next_urgent(&[], None), None);
+		assert_eq!(next_urgent(&[], Some(0xa)), None);
+	}
+}
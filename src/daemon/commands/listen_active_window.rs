@@ -0,0 +1,119 @@
+use super::prelude::*;
+use super::listener::{escape_markup, print_updates, Listener, Output, OutputFormat, Waybar};
+use crate::daemon::{ActiveWindowState, HyprState};
+
+///One line of `listen-active-window`, or `null` while nothing has focus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ActiveWindowInfo {
+	class: String,
+	///Rewritten and cut according to `[active_window]`.
+	title: String,
+	///The title as the window set it.
+	full_title: String,
+	workspace: String,
+	workspace_id: i32,
+	floating: bool,
+	fullscreen: bool,
+	pinned: bool,
+	///Windows in its group, itself included; 0 if it isn't in one.
+	group_size: usize,
+} impl ActiveWindowInfo {
+	fn new(window: &ActiveWindowState) -> Self {
+		ActiveWindowInfo {
+			class: window.class.clone(),
+			title: crate::config::current().active_window.title(&window.class, &window.title),
+			full_title: window.title.clone(),
+			workspace: window.workspace_name.clone(),
+			workspace_id: window.workspace_id,
+			floating: window.floating,
+			fullscreen: window.fullscreen,
+			pinned: window.pinned,
+			group_size: window.grouped.len(),
+		}
+	}
+}
+
+///`text` is the shortened title, `tooltip` the full one, `alt` the window class. The `floating`,
+///`fullscreen`, `pinned` and `grouped` classes are set as they apply, `empty` while nothing has
+///focus.
+fn waybar(window: Option<&ActiveWindowInfo>) -> Waybar {
+	let Some(window) = window else {
+		return Waybar { class: vec![String::from("empty")], ..Default::default() };
+	};
+	let class = [
+		(window.floating, "floating"),
+		(window.fullscreen, "fullscreen"),
+		(window.pinned, "pinned"),
+		(window.group_size > 0, "grouped"),
+	];
+	Waybar {
+		text: escape_markup(&window.title),
+		tooltip: escape_markup(&window.full_title),
+		class: class.into_iter().filter(|(set, _)| *set).map(|(_, class)| class.to_string()).collect(),
+		alt: window.class.clone(),
+		percentage: None,
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct ListenActiveWindow {
+	#[arg(long, value_enum, default_value_t)]
+	#[serde(default)]
+	format: OutputFormat,
+} impl HandleDaemon for ListenActiveWindow {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		d.add_step(ListenActiveWindowStep { listener: Listener::new(s), format: self.format });
+		Ok(())
+	}
+} impl HandleRemote for ListenActiveWindow {
+	fn remote(self, s: UnixStream) -> Result<()> {
+		print_updates::<Option<ActiveWindowInfo>>(s)
+	}
+}
+
+#[derive(Debug)]
+struct ListenActiveWindowStep {
+	listener: Listener<Output<Option<ActiveWindowInfo>>>,
+	format: OutputFormat,
+} impl ListenActiveWindowStep {
+	fn send_update(&mut self, state: &HyprState) -> Result<StepState> {
+		let window = state.active_window().map(ActiveWindowInfo::new);
+		let update = match self.format {
+			OutputFormat::Json => Output::Json(window),
+			OutputFormat::Waybar => Output::Waybar(waybar(window.as_ref())),
+		};
+		self.listener.send(state, update)
+	}
+}
+
+impl MainLoopStep for ListenActiveWindowStep {
+	fn name(&self) -> &'static str {
+		"listen-active-window"
+	}
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		if !self.listener.is_started() {
+			return self.send_update(cx.state);
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, _: &Event) -> Result<StepState> {
+		self.listener.on_event(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
+		if self.listener.is_update(timer) {
+			return self.send_update(cx.state);
+		}
+		Ok(StepState::KeepActive)
+	}
+	//The title rules may have changed.
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.listener.schedule(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_error(&mut self, error: anyhow::Error) -> Result<StepState> {
+		self.listener.send_error(&error)?;
+
+		Ok(StepState::Done)
+	}
+}
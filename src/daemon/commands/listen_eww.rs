@@ -1,9 +1,21 @@
-use std::io;
+use std::{convert::Infallible, str::FromStr};
 
 use super::prelude::*;
-use hyprctl::{Monitor, Workspace};
+use super::listener::{escape_markup, print_updates, Listener, Output, OutputFormat, Waybar};
+use crate::config::{IconConfig, WaybarConfig};
+use crate::daemon::{HyprState, MonitorState, WindowState, WorkspaceLabel, WorkspaceState};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EwwWindow {
+	//`0x`-prefixed, used in `hyprctl dispatch focuswindow address:…`
+	address: String,
+	class: String,
+	title: String,
+	urgent: bool,
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct EwwWorkspace {
 	//Used for display
 	index: Option<String>,
@@ -12,6 +24,16 @@ struct EwwWorkspace {
 
 	//Used to highlight active workspace on each monitor
 	active_on: Option<String>,
+	//Shown on its monitor, as its active or special workspace
+	visible: bool,
+	//Shown on the focused monitor
+	focused: bool,
+
+	windows: Vec<EwwWindow>,
+	//Whether any of `windows` wants attention
+	urgent: bool,
+	//Icons for the classes of `windows`, each only once
+	icons: Vec<String>,
 
 	//Changes behavior
 	is_special: bool,
@@ -21,128 +43,246 @@ struct EwwWorkspace {
 	//used in `hyprctl dispatch workspace`
 	id: i32,
 } impl EwwWorkspace {
-	pub fn new(monitors: &Vec<Monitor>, workspace: Workspace) -> Self {
-		let mut active_on = None;
-		for monitor in monitors {
-			if monitor.active_workspace.id == workspace.id {
-				active_on = Some(monitor.name.clone());
-				break;
-			} else if let Some(special) = &monitor.special_workspace && special.id == workspace.id {
-				active_on = Some(monitor.name.clone());
-				break;
+	///Index, icon and name, as far as the workspace has them.
+	fn label(&self) -> String {
+		let label = [&self.index, &self.icon, &self.name].into_iter().flatten()
+			.filter(|part| !part.is_empty())
+			.map(String::as_str)
+			.collect::<Vec<_>>()
+			.join(" ");
+		if label.is_empty() { self.special_name.clone() } else { label }
+	}
+
+	pub fn new(state: &HyprState, icons: &IconConfig, workspace: &WorkspaceState) -> Self {
+		let shown_on = state.monitors().iter()
+			.find(|m| m.active_workspace == workspace.id || m.special_workspace == Some(workspace.id));
+		let active_on = shown_on.map(|m| m.name.clone());
+		let visible = shown_on.is_some();
+		let focused = shown_on.is_some_and(|m| m.focused);
+		let is_special = workspace.id < 0;
+		let windows: Vec<&WindowState> = state.windows().iter().filter(|w| w.workspace_id == workspace.id).collect();
+		let mut window_icons: Vec<String> = vec![];
+		for window in &windows {
+			if let Some(icon) = icons.class_icon(&window.class) && !window_icons.iter().any(|i| i == icon) {
+				window_icons.push(icon.to_string());
 			}
 		}
-		let is_special = workspace.id < 0;
+		let windows: Vec<EwwWindow> = windows.into_iter()
+			.map(|w| EwwWindow {
+				address: format!("0x{:x}", w.address),
+				class: w.class.clone(),
+				title: w.title.clone(),
+				urgent: state.is_urgent(w.address),
+			})
+			.collect();
+		let urgent = windows.iter().any(|w| w.urgent);
 
 		if is_special {
 			EwwWorkspace {
 				active_on,
+				visible,
+				focused,
+				windows,
+				urgent,
+				icons: window_icons,
 				is_special,
 				index: None,
-				icon: Some(match &workspace.name[..] {
-					"special:guide" => "󰈹",
-					"special:term" => "",
-					"special:other" => "",
-					"special:music" => "",
-					"special:notes" => "",
-					"special:testing" => "",
-					n => n,
-				}.to_string()),
+				icon: Some(icons.special_icon(&workspace.name)),
 				name: None,
 				id: workspace.id,
 				special_name: if let Some((_, right)) = workspace.name.split_once(':') {
 					right.to_string()
 				} else {
-					workspace.name
+					workspace.name.clone()
 				}
 			}
-		} else if let Ok(parts) = serde_json::from_str::<[String; 3]>(&workspace.name) {
-			let [index, icon, name] = parts;
-			EwwWorkspace {
-				active_on,
-				index: if index.is_empty() {
-					None
-				} else {
-					Some(if index == "#" { workspace.id.to_string() } else { index })
-				},
-				icon: if icon.is_empty() { None } else { Some(icon) },
-				name: if name.is_empty() { None } else { Some(name) },
-				is_special,
-				id: workspace.id,
-				special_name: workspace.name,
-			}
 		} else {
+			let label = WorkspaceLabel::parse(&workspace.name);
 			EwwWorkspace {
 				active_on,
-				index: Some(workspace.name.clone()),
-				icon: None,
-				name: None,
+				visible,
+				focused,
+				windows,
+				urgent,
+				icons: window_icons,
+				index: label.index(workspace.id),
+				icon: label.icon().map(String::from),
+				name: label.name().map(String::from),
 				is_special,
 				id: workspace.id,
-				special_name: workspace.name,
+				special_name: workspace.name.clone(),
 			}
 		}
 	}
 }
 
-pub struct ListenEww;
-impl ListenEww {
+///Which monitor's workspaces `listen-eww` sends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonitorSelector {
+	///Whichever has focus at the moment.
+	Focused,
+	///The first whose description contains this.
+	Description(String),
+	Name(String),
+} impl MonitorSelector {
+	fn find<'a>(&self, state: &'a HyprState) -> Option<&'a MonitorState> {
+		let mut monitors = state.monitors().iter();
+		match self {
+			MonitorSelector::Focused => monitors.find(|m| m.focused),
+			MonitorSelector::Description(description) => monitors.find(|m| m.description.contains(description.as_str())),
+			MonitorSelector::Name(name) => monitors.find(|m| m.name == *name),
+		}
+	}
+} impl FromStr for MonitorSelector {
+	type Err = Infallible;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"focused" => MonitorSelector::Focused,
+			_ => match s.strip_prefix("desc:") {
+				Some(description) => MonitorSelector::Description(description.to_string()),
+				None => MonitorSelector::Name(s.to_string()),
+			},
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceOrder {
+	///As Hyprland lists them.
+	#[default]
+	Hyprland,
+	Id,
+	///Grouped by monitor, in the order Hyprland lists monitors, then by id.
+	Monitor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct ListenEww {
+	#[arg(long, value_enum, default_value_t)]
+	#[serde(default)]
+	format: OutputFormat,
+	///Only send workspaces on this monitor: its name, `desc:` and part of its description, or
+	///`focused`.
+	#[arg(long)]
+	#[serde(default)]
+	monitor: Option<MonitorSelector>,
+	#[arg(long, value_enum, default_value_t)]
+	#[serde(default)]
+	order: WorkspaceOrder,
+	///Put special workspaces after all others.
+	#[arg(long)]
+	#[serde(default)]
+	specials_last: bool,
+} impl ListenEww {
+	///The workspaces to send, in order.
+	fn select<'a>(&self, state: &'a HyprState, include_special: bool) -> Vec<&'a WorkspaceState> {
+		let monitor = self.monitor.as_ref().map(|selector| selector.find(state));
+		let mut workspaces: Vec<&WorkspaceState> = state.workspaces().iter()
+			.filter(|w| include_special || w.id >= 0)
+			.filter(|w| monitor.is_none_or(|m| m.is_some_and(|m| m.name == w.monitor)))
+			.collect();
+
+		match self.order {
+			WorkspaceOrder::Hyprland => {},
+			WorkspaceOrder::Id => workspaces.sort_by_key(|w| w.id),
+			WorkspaceOrder::Monitor => workspaces.sort_by_key(|w| {
+				(state.monitors().iter().position(|m| m.name == w.monitor), w.id)
+			}),
+		}
+		if self.specials_last {
+			workspaces.sort_by_key(|w| w.id < 0);
+		}
+		workspaces
+	}
 } impl HandleDaemon for ListenEww {
-	fn daemon(d: &mut Daemon, s: UnixStream) -> Result<()> {
-		d.steps.push(Box::new(ListenEwwStep { socket: s }));
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		d.add_step(ListenEwwStep { listener: Listener::new(s), command: self });
 		Ok(())
 	}
 } impl HandleRemote for ListenEww {
-	fn remote(mut s: UnixStream) -> Result<()> {
-		loop {
-			let update: ListenEwwMessage = recv!(s)?;
-			match update {
-				Ok(u) => println!("{}", serde_json::to_string(&u)?),
-				Err(e) => bail!(e),
-			}
-		}
+	fn remote(self, s: UnixStream) -> Result<()> {
+		print_updates::<Vec<EwwWorkspace>>(s)
 	}
 }
 
-type ListenEwwMessage = Result<Vec<EwwWorkspace>, String>;
-
 #[derive(Debug)]
 struct ListenEwwStep {
-	socket: UnixStream
+	listener: Listener<Output<Vec<EwwWorkspace>>>,
+	command: ListenEww,
 } impl ListenEwwStep {
-	fn send_update(&mut self) -> Result<()> {
-		let monitors = hyprctl::monitors()?;
-		let update: Vec<EwwWorkspace> = hyprctl::workspaces()?.into_iter()
-			.map(|w| EwwWorkspace::new(&monitors, w))
+	fn send_update(&mut self, state: &HyprState) -> Result<StepState> {
+		let config = crate::config::current();
+		let workspaces: Vec<EwwWorkspace> = self.command.select(state, config.eww.include_special).into_iter()
+			.map(|w| EwwWorkspace::new(state, &config.icons, w))
 			.collect();
-
-		send!(self.socket, ListenEwwMessage::Ok(update))?;
-		Ok(())
+		let update = match self.command.format {
+			OutputFormat::Json => Output::Json(workspaces),
+			OutputFormat::Waybar => Output::Waybar(waybar(&config.waybar, &workspaces)),
+		};
+		self.listener.send(state, update)
 	}
 }
 
-impl MainLoopStep for ListenEwwStep {
-	fn on_event(&mut self, event: &Event) -> Result<StepState> {
-		if let
-			Event::FocusedMon { .. } |
-			Event::Workspace { .. } |
-			Event::CreateWorkspace { .. } |
-			Event::DestroyWorkspace { .. } |
-			Event::RenameWorkspace { .. } |
-			Event::ActiveSpecial { .. } = event
-		{
-			if let Err(e) = self.send_update() &&
-				let Some(e) = e.downcast_ref::<std::io::Error>() &&
-				e.kind() == io::ErrorKind::BrokenPipe
-			{
-				return Ok(StepState::Done);
+///All workspaces in `text`, the active ones per monitor in `tooltip`. `alt` is the focused one's
+///label. The `special` class is set while a special workspace is shown there, and `urgent` while
+///a window on any of them wants attention.
+fn waybar(config: &WaybarConfig, workspaces: &[EwwWorkspace]) -> Waybar {
+	let mut output = Waybar::default();
+	let mut tooltip = vec![];
+	for workspace in workspaces {
+		let label = workspace.label();
+		let template = if workspace.focused { &config.active_workspace } else { &config.workspace };
+
+		if !output.text.is_empty() {
+			output.text.push_str(&config.separator);
+		}
+		output.text.push_str(&template.replace("{label}", &escape_markup(&label)));
+		if let Some(monitor) = &workspace.active_on {
+			tooltip.push(format!("{}: {}", escape_markup(monitor), escape_markup(&label)));
+		}
+		if workspace.urgent && !output.class.iter().any(|c| c == "urgent") {
+			output.class.push(String::from("urgent"));
+		}
+		if workspace.focused {
+			if workspace.is_special {
+				output.class.push(String::from("special"));
+			} else {
+				output.alt = label;
 			}
 		}
+	}
+	output.tooltip = tooltip.join("\n");
+	output
+}
 
+impl MainLoopStep for ListenEwwStep {
+	fn name(&self) -> &'static str {
+		"listen-eww"
+	}
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		if !self.listener.is_started() {
+			return self.send_update(cx.state);
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, _: &Event) -> Result<StepState> {
+		self.listener.on_event(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
+		if self.listener.is_update(timer) {
+			return self.send_update(cx.state);
+		}
+		Ok(StepState::KeepActive)
+	}
+	//Icons or `include_special` may have changed.
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.listener.schedule(cx);
 		Ok(StepState::KeepActive)
 	}
 	fn on_error(&mut self, error: anyhow::Error) -> Result<StepState> {
-		send!(self.socket, ListenEwwMessage::Err(format!("{error:#}")))?;
+		self.listener.send_error(&error)?;
 
 		Ok(StepState::Done)
 	}
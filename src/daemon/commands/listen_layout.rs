@@ -0,0 +1,120 @@
+use super::prelude::*;
+use super::listener::{escape_markup, print_updates, Listener, Output, OutputFormat, Waybar};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct KeyboardLayout {
+	keyboard: String,
+	///As Hyprland names it, like `English (US)`.
+	layout: String,
+	///From `[layout] codes`, or `layout` if it has none.
+	code: String,
+	///The keyboard Hyprland's layout dispatchers act on by default.
+	main: bool,
+}
+
+///`text` and `alt` are the main keyboard's code, which is also set as a class; `tooltip` lists
+///every keyboard's layout.
+fn waybar(keyboards: &[KeyboardLayout]) -> Waybar {
+	let Some(main) = keyboards.iter().find(|k| k.main).or(keyboards.first()) else {
+		return Waybar { class: vec![String::from("empty")], ..Default::default() };
+	};
+	Waybar {
+		text: escape_markup(&main.code),
+		tooltip: keyboards.iter()
+			.map(|k| format!("{}: {}", escape_markup(&k.keyboard), escape_markup(&k.layout)))
+			.collect::<Vec<_>>()
+			.join("\n"),
+		class: vec![main.code.clone()],
+		alt: main.code.clone(),
+		percentage: None,
+	}
+}
+
+fn keyboards() -> Result<Vec<KeyboardLayout>> {
+	Ok(hyprctl::devices()?.keyboards.into_iter()
+		.map(|keyboard| KeyboardLayout {
+			keyboard: keyboard.name,
+			layout: keyboard.active_keymap,
+			code: String::new(),
+			main: keyboard.main,
+		})
+		.collect())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct ListenLayout {
+	#[arg(long, value_enum, default_value_t)]
+	#[serde(default)]
+	format: OutputFormat,
+} impl HandleDaemon for ListenLayout {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		d.add_step(ListenLayoutStep { listener: Listener::new(s), format: self.format, keyboards: keyboards()? });
+		Ok(())
+	}
+} impl HandleRemote for ListenLayout {
+	fn remote(self, s: UnixStream) -> Result<()> {
+		print_updates::<Vec<KeyboardLayout>>(s)
+	}
+}
+
+#[derive(Debug)]
+struct ListenLayoutStep {
+	listener: Listener<Output<Vec<KeyboardLayout>>>,
+	format: OutputFormat,
+	///Kept up to date from `activelayout` events, and queried again on reload or when an unknown
+	///keyboard shows up. `code` is filled in when sent.
+	keyboards: Vec<KeyboardLayout>,
+} impl ListenLayoutStep {
+	fn send_update(&mut self, cx: &Context) -> Result<StepState> {
+		let config = crate::config::current();
+		for keyboard in &mut self.keyboards {
+			keyboard.code = config.layout.code(&keyboard.layout);
+		}
+		let update = match self.format {
+			OutputFormat::Json => Output::Json(self.keyboards.clone()),
+			OutputFormat::Waybar => Output::Waybar(waybar(&self.keyboards)),
+		};
+		self.listener.send(cx.state, update)
+	}
+}
+
+impl MainLoopStep for ListenLayoutStep {
+	fn name(&self) -> &'static str {
+		"listen-layout"
+	}
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		if !self.listener.is_started() {
+			return self.send_update(cx);
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
+		let Event::ActiveLayout { keyboard_name, layout_name } = event else {
+			return Ok(StepState::KeepActive);
+		};
+		match self.keyboards.iter_mut().find(|k| k.keyboard == *keyboard_name) {
+			Some(keyboard) => keyboard.layout.clone_from(layout_name),
+			//Plugged in since they were queried; others may be gone.
+			None => self.keyboards = keyboards()?,
+		}
+		self.listener.schedule(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
+		if self.listener.is_update(timer) {
+			return self.send_update(cx);
+		}
+		Ok(StepState::KeepActive)
+	}
+	//The codes may have changed, and keyboards unplugged since are dropped.
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.keyboards = keyboards()?;
+		self.listener.schedule(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_error(&mut self, error: anyhow::Error) -> Result<StepState> {
+		self.listener.send_error(&error)?;
+
+		Ok(StepState::Done)
+	}
+}
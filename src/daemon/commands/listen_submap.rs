@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+use super::prelude::*;
+use super::listener::{escape_markup, print_updates, Listener, Output, OutputFormat, Waybar};
+use crate::hyprctl::Bind;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SubmapBind {
+	modifiers: Vec<String>,
+	key: String,
+	description: String,
+}
+
+///One line of `listen-submap`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SubmapInfo {
+	///`None` outside of submaps.
+	name: Option<String>,
+	///How long the submap has been active when this was sent.
+	active_ms: u64,
+	///Only binds with a description, as the panel shows them.
+	binds: Vec<SubmapBind>,
+}
+
+///`text` and `alt` are the submap's name, `tooltip` its binds. The `active` class is set while in
+///a submap, `empty` otherwise.
+fn waybar(submap: &SubmapInfo) -> Waybar {
+	let Some(name) = &submap.name else {
+		return Waybar { class: vec![String::from("empty")], ..Default::default() };
+	};
+	Waybar {
+		text: escape_markup(name),
+		tooltip: submap.binds.iter()
+			.map(|bind| {
+				let mut keys = bind.modifiers.clone();
+				keys.push(bind.key.clone());
+				format!("{}  {}", escape_markup(&keys.join(" + ")), escape_markup(&bind.description))
+			})
+			.collect::<Vec<_>>()
+			.join("\n"),
+		class: vec![String::from("active")],
+		alt: name.clone(),
+		percentage: None,
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct ListenSubmap {
+	#[arg(long, value_enum, default_value_t)]
+	#[serde(default)]
+	format: OutputFormat,
+	///While in a submap, send it again this often, in milliseconds, to update `active_ms`. Only
+	///changes are sent if 0.
+	#[arg(long, default_value_t)]
+	#[serde(default)]
+	interval: u64,
+} impl HandleDaemon for ListenSubmap {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		let submap = match hyprctl::submap() {
+			Ok(submap) => submap,
+			//Hyprland before 0.40 can't tell; the next `submap` event corrects it.
+			Err(e) if matches!(e.downcast_ref(), Some(hyprctl::Error::Rejected(_))) => None,
+			Err(e) => return Err(e),
+		};
+		d.add_step(ListenSubmapStep {
+			listener: Listener::new(s),
+			format: self.format,
+			interval: Duration::from_millis(self.interval),
+			//When it was entered is unknown, so it counts from now.
+			submap,
+			entered: Instant::now(),
+			binds: None,
+			tick: None,
+		});
+		Ok(())
+	}
+} impl HandleRemote for ListenSubmap {
+	fn remote(self, s: UnixStream) -> Result<()> {
+		print_updates::<SubmapInfo>(s)
+	}
+}
+
+#[derive(Debug)]
+struct ListenSubmapStep {
+	listener: Listener<Output<SubmapInfo>>,
+	format: OutputFormat,
+	interval: Duration,
+	submap: Option<String>,
+	entered: Instant,
+	///Fetched on first use, dropped on reload.
+	binds: Option<Vec<Bind>>,
+	///Repeats the update while in a submap, if there is an interval.
+	tick: Option<TimerId>,
+} impl ListenSubmapStep {
+	fn send_update(&mut self, cx: &mut Context) -> Result<StepState> {
+		let binds = match (&self.submap, &self.binds) {
+			(None, _) => vec![],
+			(Some(name), Some(binds)) => described_binds(binds, name),
+			(Some(name), None) => described_binds(self.binds.insert(hyprctl::binds()?), name),
+		};
+		let submap = SubmapInfo {
+			name: self.submap.clone(),
+			active_ms: if self.submap.is_some() {
+				u64::try_from(self.entered.elapsed().as_millis()).unwrap_or(u64::MAX)
+			} else {
+				0
+			},
+			binds,
+		};
+
+		if self.submap.is_some() && !self.interval.is_zero() {
+			if !self.tick.is_some_and(|tick| cx.timers.is_pending(tick)) {
+				self.tick = Some(cx.timers.every(self.interval));
+			}
+		} else if let Some(tick) = self.tick.take() {
+			cx.timers.cancel(tick);
+		}
+
+		let update = match self.format {
+			OutputFormat::Json => Output::Json(submap),
+			OutputFormat::Waybar => Output::Waybar(waybar(&submap)),
+		};
+		self.listener.send(cx.state, update)
+	}
+}
+
+fn described_binds(binds: &[Bind], submap: &str) -> Vec<SubmapBind> {
+	binds.iter()
+		.filter(|bind| bind.submap.as_deref() == Some(submap))
+		.filter_map(|bind| Some(SubmapBind {
+			modifiers: bind.modifiers().into_iter().map(String::from).collect(),
+			key: bind.key.clone(),
+			description: bind.description.clone()?,
+		}))
+		.collect()
+}
+
+impl MainLoopStep for ListenSubmapStep {
+	fn name(&self) -> &'static str {
+		"listen-submap"
+	}
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		if !self.listener.is_started() {
+			return self.send_update(cx);
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
+		match event {
+			Event::Submap { name } if *name != self.submap => {
+				self.submap.clone_from(name);
+				self.entered = Instant::now();
+				self.listener.schedule(cx);
+			},
+			Event::ConfigReloaded => {
+				self.binds = None;
+				self.listener.schedule(cx);
+			},
+			_ => {},
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
+		if self.listener.is_update(timer) || self.tick == Some(timer) {
+			return self.send_update(cx);
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.binds = None;
+		self.listener.schedule(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_error(&mut self, error: anyhow::Error) -> Result<StepState> {
+		self.listener.send_error(&error)?;
+
+		Ok(StepState::Done)
+	}
+}
@@ -0,0 +1,124 @@
+use std::io;
+
+use super::prelude::*;
+use crate::daemon::HyprState;
+
+///How a `listen-*` command prints its updates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+	///The stream's own JSON, as eww's `deflisten` takes it.
+	#[default]
+	Json,
+	///Waybar's custom module protocol, for `"return-type": "json"`.
+	Waybar,
+}
+
+///One line of a `listen-*` stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Output<T> {
+	Json(T),
+	Waybar(Waybar),
+}
+
+///See `waybar-custom(5)`. `text` and `tooltip` may use Pango markup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Waybar {
+	pub text: String,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub tooltip: String,
+	///CSS classes for the module.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub class: Vec<String>,
+	///Picks an icon from `format-icons`.
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub alt: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub percentage: Option<u8>,
+}
+
+///Escapes text for Pango markup.
+pub fn escape_markup(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'\'' => escaped.push_str("&apos;"),
+			'"' => escaped.push_str("&quot;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+///Prints every update of a `listen-*` stream until the daemon goes away.
+pub fn print_updates<T: serde::de::DeserializeOwned + Serialize>(mut s: UnixStream) -> Result<()> {
+	loop {
+		let update: Output<T> = recv!(s)?;
+		println!("{}", serde_json::to_string(&update)?);
+	}
+}
+
+///What the `listen-*` steps share: updates are computed from [`HyprState`] once per batch of
+///events, and only sent if they differ from the last one.
+#[derive(Debug)]
+pub struct Listener<T> {
+	socket: Client,
+	///Hyprland sends several events for one change; they all share one update.
+	update: Option<TimerId>,
+	///What the listener has, `None` until the first update.
+	sent: Option<T>,
+	///[`HyprState::generation`] of the last update.
+	generation: u64,
+} impl<T: Serialize + PartialEq> Listener<T> {
+	pub fn new(socket: Client) -> Self {
+		Listener { socket, update: None, sent: None, generation: 0 }
+	}
+
+	pub fn is_started(&self) -> bool {
+		self.sent.is_some()
+	}
+
+	///Sends `update` unless the listener already has it. Finishes once the listener is gone.
+	pub fn send(&mut self, state: &HyprState, update: T) -> Result<StepState> {
+		self.generation = state.generation();
+		if self.sent.as_ref() == Some(&update) {
+			return Ok(StepState::KeepActive);
+		}
+
+		match send!(self.socket, update) {
+			Ok(()) => {},
+			Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => {
+				return Ok(StepState::Done);
+			},
+			Err(e) => return Err(e),
+		}
+		self.sent = Some(update);
+		Ok(StepState::KeepActive)
+	}
+
+	///Makes [`is_update`](Self::is_update) fire once the current batch of events is handled.
+	pub fn schedule(&mut self, cx: &mut Context) {
+		if !self.update.is_some_and(|update| cx.timers.is_pending(update)) {
+			self.update = Some(cx.timers.at(std::time::Instant::now()));
+		}
+	}
+
+	///Schedules an update if the state changed since the last one.
+	pub fn on_event(&mut self, cx: &mut Context) {
+		if cx.state.generation() != self.generation {
+			self.schedule(cx);
+		}
+	}
+
+	pub fn is_update(&self, timer: TimerId) -> bool {
+		self.update == Some(timer)
+	}
+
+	pub fn send_error(&self, error: &anyhow::Error) -> Result<()> {
+		send_error!(self.socket, error)
+	}
+}
@@ -1,6 +1,6 @@
 use std::os::unix::net::UnixStream;
 use anyhow::Result;
-use crate::daemon::Daemon;
+use crate::daemon::{client::Client, Daemon};
 
 pub mod prelude {
 	#![allow(unused_imports)]
@@ -9,59 +9,89 @@ pub mod prelude {
 	pub use anyhow::{anyhow, bail, Result};
 
 	pub use crate::hyprctl::prelude::*;
-	pub use crate::{send, recv};
-	pub use crate::daemon::Daemon;
+	pub use crate::{send, send_error, recv};
+	pub use crate::daemon::{client::Client, Daemon};
 
 	pub use super::{HandleDaemon, HandleRemote};
-	pub use crate::daemon::{MainLoopStep, StepState};
+	pub use crate::daemon::{Context, MainLoopStep, StepState, Supervision, TimerId};
 	pub use std::os::unix::net::{UnixStream, UnixListener};
 }
 
+mod control;
 mod hyprctl;
+mod listener;
 mod listen_eww;
+mod listen_active_window;
+mod listen_layout;
+mod listen_submap;
+mod workspace;
 
 pub mod implementors {
+	pub use super::control::*;
 	pub use super::hyprctl::*;
 	pub use super::listen_eww::*;
+	pub use super::listen_active_window::*;
+	pub use super::listen_layout::*;
+	pub use super::listen_submap::*;
+	pub use super::workspace::*;
 }
 
+///Queues `$data` for the remote, wrapped in a successful [`Reply`](crate::daemon::reply::Reply).
 #[macro_export]
 macro_rules! send {
     ($socket:expr, $data:expr) => {
-        ciborium::into_writer(&$data, &mut $socket)
+        $socket.send(&$data)
     };
 }
 
+///Queues an `anyhow::Error` for the remote as a failed [`Reply`](crate::daemon::reply::Reply).
+#[macro_export]
+macro_rules! send_error {
+    ($socket:expr, $error:expr) => {
+        $socket.send_error($error)
+    };
+}
+
+///Receives one [`Reply`](crate::daemon::reply::Reply) from the daemon, turning a reported error
+///into an `Err`.
 #[macro_export]
 macro_rules! recv {
     ($socket:expr) => {
-        ciborium::from_reader(&mut $socket)
+        $crate::daemon::reply::receive(&mut $socket)
     };
 }
 
 pub trait HandleRemote {
-	fn remote(s: UnixStream) -> Result<()>;
+	fn remote(self, s: UnixStream) -> Result<()>;
 }
 
 pub trait HandleDaemon {
-	fn daemon(d: &mut Daemon, s: UnixStream) -> Result<()>;
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()>;
 }
 
 macro_rules! command_enum {
-	{ $($name:ident),* $(,)* } => {
-		#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, clap::ValueEnum)]
+	{ $($(#[$meta:meta])* $name:ident),* $(,)* } => {
+		#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, clap::Subcommand)]
 		pub enum Command {
-			$($name),*
+			$($(#[$meta])* $name($name)),*
 		}
 		impl Command {
+			///Every command this build can serve, as advertised during the handshake.
+			pub const CAPABILITIES: &[&str] = &[$(stringify!($name)),*];
+
+			pub fn capability(&self) -> &'static str {
+				match self {
+					$(Command::$name(_) => stringify!($name)),*
+				}
+			}
 			pub fn dispatch_remote(self, socket: UnixStream) -> Result<()> {
 				match self {
-					$(Command::$name => <$name as HandleRemote>::remote(socket)),*
+					$(Command::$name(command) => command.remote(socket)),*
 				}
 			}
-			pub fn dispatch_daemon(self, daemon: &mut Daemon, socket: UnixStream) -> Result<()> {
+			pub fn dispatch_daemon(self, daemon: &mut Daemon, socket: Client) -> Result<()> {
 				match self {
-					$(Command::$name => <$name as HandleDaemon>::daemon(daemon, socket)),*
+					$(Command::$name(command) => command.daemon(daemon, socket)),*
 				}
 			}
 		}
@@ -74,5 +104,25 @@ use implementors::*;
 command_enum! {
 	Workspaces,
 	Monitors,
+	///Change a workspace.
+	Workspace,
 	ListenEww,
+	///Stream the focused window's class, title and state as JSON lines.
+	ListenActiveWindow,
+	///Stream each keyboard's active layout as JSON lines.
+	ListenLayout,
+	///Stream the current submap, how long it has been active and its binds as JSON lines.
+	ListenSubmap,
+	Reload,
+	Quit,
+	///Print the daemon's recent log records.
+	Logs,
+	///Show what the daemon is running and how it is doing.
+	Status,
+	///Show a Hyprland notification.
+	Notify,
+	///Dismiss Hyprland notifications.
+	DismissNotify,
+	///Focus the window that has wanted attention the longest; again for the next one.
+	FocusUrgent,
 }
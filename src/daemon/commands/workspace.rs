@@ -0,0 +1,86 @@
+use super::prelude::*;
+use crate::daemon::{reply::RemoteError, WorkspaceLabel};
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+pub struct Workspace {
+	#[command(subcommand)]
+	action: WorkspaceAction,
+} impl HandleDaemon for Workspace {
+	fn daemon(self, d: &mut Daemon, s: Client) -> Result<()> {
+		match self.action {
+			WorkspaceAction::SetLabel(command) => command.daemon(d, s),
+		}
+	}
+} impl HandleRemote for Workspace {
+	fn remote(self, s: UnixStream) -> Result<()> {
+		match self.action {
+			WorkspaceAction::SetLabel(command) => command.remote(s),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Subcommand)]
+enum WorkspaceAction {
+	///Rename a workspace to the `[index, icon, name]` label bars show. Parts that aren't given are
+	///kept; pass an empty one to clear it.
+	SetLabel(SetLabel),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, clap::Args)]
+struct SetLabel {
+	///`#` shows the workspace's id.
+	#[arg(long)]
+	index: Option<String>,
+	#[arg(long)]
+	icon: Option<String>,
+	#[arg(long)]
+	name: Option<String>,
+	///Id or name of the workspace. The focused one if unset.
+	workspace: Option<String>,
+} impl SetLabel {
+	fn target(&self) -> Result<hyprctl::Workspace> {
+		let workspaces = hyprctl::workspaces()?;
+		let id = match &self.workspace {
+			Some(which) => match workspaces.iter().find(|w| w.name == *which || w.id.to_string() == *which) {
+				Some(workspace) => workspace.id,
+				None => return Err(RemoteError::request(format!("No workspace is called {which}"))),
+			},
+			None => match hyprctl::monitors()?.into_iter().find(|m| m.focused) {
+				Some(monitor) => monitor.active_workspace.id,
+				None => bail!("No monitor has focus"),
+			},
+		};
+		workspaces.into_iter().find(|w| w.id == id).ok_or_else(|| anyhow!("Workspace {id} is gone"))
+	}
+} impl HandleDaemon for SetLabel {
+	fn daemon(self, _: &mut Daemon, s: Client) -> Result<()> {
+		let workspace = self.target()?;
+		if workspace.id < 0 {
+			let message = format!("{} is a special workspace, which can't be renamed", workspace.name);
+			return Err(RemoteError::request(message));
+		}
+
+		let mut label = WorkspaceLabel::parse(&workspace.name);
+		if let Some(index) = self.index {
+			label.index = index;
+		}
+		if let Some(icon) = self.icon {
+			label.icon = icon;
+		}
+		if let Some(name) = self.name {
+			label.name = name;
+		}
+		if label == WorkspaceLabel::default() {
+			return Err(RemoteError::request("A workspace's label can't be empty"));
+		}
+
+		hyprctl::dispatch("renameworkspace", &format!("{} {label}", workspace.id))?;
+		send!(s, ())?;
+		Ok(())
+	}
+} impl HandleRemote for SetLabel {
+	fn remote(self, mut s: UnixStream) -> Result<()> {
+		let (): () = recv!(s)?;
+		Ok(())
+	}
+}
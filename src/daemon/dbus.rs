@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use anyhow::{Context as _, Result};
+use zbus::{
+	blocking::{connection, object_server::InterfaceRef, Connection},
+	fdo,
+	interface,
+	object_server::SignalEmitter,
+	zvariant::{OwnedValue, Type, Value},
+};
+
+use crate::{config, hyprctl::{self, Event}, info, log_error};
+
+use super::{state::{MonitorState, WorkspaceState}, Context, Daemon, HyprState, MainLoopStep, StepState, Supervision};
+
+const NAME: &str = "org.homehelper.Daemon";
+const PATH: &str = "/org/homehelper/Daemon";
+
+///`(issub)` on the bus.
+#[derive(Debug, Clone, PartialEq, Eq, Type, Value, OwnedValue)]
+pub struct DbusWorkspace {
+	id: i32,
+	name: String,
+	monitor: String,
+	windows: u32,
+	has_fullscreen: bool,
+} impl DbusWorkspace {
+	fn new(workspace: &WorkspaceState, state: &HyprState) -> Self {
+		let windows = || state.windows().iter().filter(|w| w.workspace_id == workspace.id);
+		DbusWorkspace {
+			id: workspace.id,
+			name: workspace.name.clone(),
+			monitor: workspace.monitor.clone(),
+			windows: u32::try_from(windows().count()).unwrap_or(u32::MAX),
+			has_fullscreen: windows().any(|w| w.fullscreen),
+		}
+	}
+}
+
+///`(issuudisbb)` on the bus. `special_workspace` is empty unless one is shown.
+#[derive(Debug, Clone, PartialEq, Type, Value, OwnedValue)]
+pub struct DbusMonitor {
+	id: i32,
+	name: String,
+	description: String,
+	width: u32,
+	height: u32,
+	scale: f64,
+	active_workspace: i32,
+	special_workspace: String,
+	focused: bool,
+	enabled: bool,
+} impl DbusMonitor {
+	fn new(monitor: &MonitorState, state: &HyprState) -> Self {
+		let special_workspace = monitor.special_workspace
+			.and_then(|id| state.workspaces().iter().find(|w| w.id == id))
+			.map(|w| w.name.clone());
+		DbusMonitor {
+			id: monitor.id,
+			name: monitor.name.clone(),
+			description: monitor.description.clone(),
+			width: monitor.width,
+			height: monitor.height,
+			scale: monitor.scale.into(),
+			active_workspace: monitor.active_workspace,
+			special_workspace: special_workspace.unwrap_or_default(),
+			focused: monitor.focused,
+			enabled: monitor.enabled,
+		}
+	}
+}
+
+fn failed(error: &anyhow::Error) -> fdo::Error {
+	fdo::Error::Failed(format!("{error:#}"))
+}
+
+///What the bus sees. Methods run on zbus' own thread, so they only talk to Hyprland; the
+///properties are kept up to date by [`DbusStep`].
+#[derive(Debug, Default)]
+struct DaemonInterface {
+	workspaces: Vec<DbusWorkspace>,
+	monitors: Vec<DbusMonitor>,
+	submap: String,
+}
+
+#[interface(name = "org.homehelper.Daemon")]
+#[allow(clippy::unused_self)] //zbus wants methods.
+impl DaemonInterface {
+	#[zbus(property)]
+	fn workspaces(&self) -> Vec<DbusWorkspace> {
+		self.workspaces.clone()
+	}
+
+	#[zbus(property)]
+	fn monitors(&self) -> Vec<DbusMonitor> {
+		self.monitors.clone()
+	}
+
+	///Empty outside of submaps.
+	#[zbus(property)]
+	fn submap(&self) -> String {
+		self.submap.clone()
+	}
+
+	fn dispatch(&self, dispatcher: &str, args: &str) -> fdo::Result<()> {
+		hyprctl::dispatch(dispatcher, args).map_err(|e| failed(&e))
+	}
+
+	///`icon` is one of `homehelper remote notify --icon`'s values and `color` anything its
+	///`--color` takes; either may be empty for the default.
+	fn notify(&self, message: &str, icon: &str, duration_ms: u32, color: &str) -> fdo::Result<()> {
+		let icon = if icon.is_empty() {
+			hyprctl::NotifyIcon::default()
+		} else {
+			clap::ValueEnum::from_str(icon, true).map_err(fdo::Error::InvalidArgs)?
+		};
+		let color = if color.is_empty() {
+			None
+		} else {
+			Some(color.parse().map_err(|e| fdo::Error::InvalidArgs(format!("{e}")))?)
+		};
+		hyprctl::send_notification(&hyprctl::Notification {
+			icon,
+			time: std::time::Duration::from_millis(duration_ms.into()),
+			color,
+			font_size: None,
+			message: message.to_string(),
+		}).map_err(|e| failed(&e))
+	}
+
+	///Every Hyprland event, with the same fields hooks get.
+	#[zbus(signal)]
+	async fn event(emitter: &SignalEmitter<'_>, kind: &str, fields: HashMap<&str, &str>) -> zbus::Result<()>;
+}
+
+///Owns the bus connection; dropping it gives up the name.
+pub struct DbusStep {
+	connection: Connection,
+	interface: InterfaceRef<DaemonInterface>,
+	///[`HyprState::generation`] the properties were last refreshed at.
+	generation: u64,
+} impl DbusStep {
+	pub fn connect(state: &HyprState) -> Result<Self> {
+		let connection = connection::Builder::session()
+			.and_then(|builder| builder.name(NAME))
+			.and_then(|builder| builder.serve_at(PATH, DaemonInterface::default()))
+			.and_then(connection::Builder::build)
+			.with_context(|| format!("Could not register {NAME} on the session bus"))?;
+		let interface = connection.object_server().interface(PATH)?;
+		let mut step = DbusStep { connection, interface, generation: 0 };
+		step.refresh(state)?;
+		Ok(step)
+	}
+
+	///Copies workspaces and monitors from `state`, announcing the properties that changed.
+	fn refresh(&mut self, state: &HyprState) -> Result<()> {
+		self.generation = state.generation();
+		let workspaces: Vec<DbusWorkspace> = state.workspaces().iter().map(|w| DbusWorkspace::new(w, state)).collect();
+		let monitors: Vec<DbusMonitor> = state.monitors().iter().map(|m| DbusMonitor::new(m, state)).collect();
+
+		let emitter = self.interface.signal_emitter();
+		let mut interface = self.interface.get_mut();
+		if interface.workspaces != workspaces {
+			interface.workspaces = workspaces;
+			zbus::block_on(interface.workspaces_changed(emitter))?;
+		}
+		if interface.monitors != monitors {
+			interface.monitors = monitors;
+			zbus::block_on(interface.monitors_changed(emitter))?;
+		}
+		Ok(())
+	}
+} impl std::fmt::Debug for DbusStep {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DbusStep")
+			.field("generation", &self.generation)
+			.finish_non_exhaustive()
+	}
+} impl MainLoopStep for DbusStep {
+	fn name(&self) -> &'static str {
+		"dbus"
+	}
+	fn supervision(&self) -> Supervision {
+		Supervision::Restart
+	}
+	//Runs once the events of a batch are handled and the state is synced.
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		if cx.state.generation() != self.generation {
+			self.refresh(cx.state)?;
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, _: &mut Context, event: &Event) -> Result<StepState> {
+		let fields = event.fields();
+		let fields: HashMap<&str, &str> = fields.iter().map(|(name, value)| (*name, value.as_str())).collect();
+		zbus::block_on(DaemonInterface::event(self.interface.signal_emitter(), event.kind(), fields))?;
+
+		if let Event::Submap { name } = event {
+			let mut interface = self.interface.get_mut();
+			interface.submap = name.clone().unwrap_or_default();
+			zbus::block_on(interface.submap_changed(self.interface.signal_emitter()))?;
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_reload(&mut self, _: &mut Context) -> Result<StepState> {
+		Ok(if config::current().dbus.enabled { StepState::KeepActive } else { StepState::Done })
+	}
+	fn restart(&mut self, cx: &mut Context) -> Result<()> {
+		//The bus may be gone already.
+		let _ = self.connection.release_name(NAME);
+		*self = Self::connect(cx.state)?;
+		Ok(())
+	}
+}
+
+///Adds the D-Bus step if it is enabled.
+pub fn start(daemon: &mut Daemon) {
+	if !config::current().dbus.enabled {
+		return;
+	}
+	match DbusStep::connect(&daemon.state) {
+		Ok(step) => {
+			info!("Registered {NAME} on the session bus");
+			daemon.add_step(step);
+		},
+		Err(e) => log_error!(&e),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::{BufRead, BufReader}, process::{Command, Stdio}};
+
+	use zbus::{blocking::Proxy, proxy::CacheProperties};
+
+	use super::*;
+	use crate::daemon::{state::WindowState, Timers};
+
+	fn seeded() -> HyprState {
+		let workspace = |id, name: &str| WorkspaceState { id, name: name.to_string(), monitor: String::from("DP-1") };
+		HyprState::seeded(
+			vec![workspace(1, "1"), workspace(2, "2")],
+			vec![MonitorState {
+				id: 0,
+				name: String::from("DP-1"),
+				description: String::from("Some monitor"),
+				width: 2560,
+				height: 1440,
+				scale: 1.0,
+				active_workspace: 1,
+				special_workspace: None,
+				focused: true,
+				enabled: true,
+			}],
+			vec![WindowState {
+				address: 0xa,
+				class: String::from("kitty"),
+				title: String::from("~"),
+				workspace_id: 1,
+				fullscreen: true,
+			}],
+		)
+	}
+
+	#[test]
+	fn signatures_match_their_docs() {
+		assert_eq!(DbusWorkspace::SIGNATURE.to_string(), "(issub)");
+		assert_eq!(DbusMonitor::SIGNATURE.to_string(), "(issuudisbb)");
+	}
+
+	#[test]
+	#[ignore = "starts a dbus-daemon"]
+	fn properties_follow_the_state() -> Result<()> {
+		let mut bus = Command::new("dbus-daemon")
+			.args(["--session", "--print-address", "--nofork"])
+			.stdout(Stdio::piped())
+			.spawn()?;
+		let mut address = String::new();
+		BufReader::new(bus.stdout.take().unwrap()).read_line(&mut address)?;
+		//SAFETY: no other test touches the environment.
+		unsafe { std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim()) };
+
+		let mut state = seeded();
+		let mut step = DbusStep::connect(&state)?;
+		let client = Connection::session()?;
+		let proxy = zbus::blocking::proxy::Builder::<Proxy>::new(&client)
+			.destination(NAME)?
+			.path(PATH)?
+			.interface(NAME)?
+			.cache_properties(CacheProperties::No)
+			.build()?;
+
+		let workspaces: Vec<DbusWorkspace> = proxy.get_property("Workspaces")?;
+		assert_eq!(workspaces.iter().map(|w| (w.id, w.windows, w.has_fullscreen)).collect::<Vec<_>>(), [(1, 1, true), (2, 0, false)]);
+		let monitors: Vec<DbusMonitor> = proxy.get_property("Monitors")?;
+		assert_eq!(monitors.iter().map(|m| (m.name.as_str(), m.active_workspace)).collect::<Vec<_>>(), [("DP-1", 1)]);
+
+		state.apply(&Event::RenameWorkspace { id: 2, new_name: String::from("web") });
+		step.step(&mut Context { timers: &mut Timers::default(), state: &state })?;
+		let workspaces: Vec<DbusWorkspace> = proxy.get_property("Workspaces")?;
+		assert_eq!(workspaces[1].name, "web");
+
+		drop(step);
+		bus.kill()?;
+		bus.wait()?;
+		Ok(())
+	}
+}
@@ -0,0 +1,81 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+///Bumped whenever anything sent over the homehelper socket after the handshake changes shape.
+///The handshake frames themselves must never change, so that mismatched binaries can always tell
+///each other what went wrong.
+pub const PROTOCOL_VERSION: u32 = 6;
+pub const BINARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+///First frame sent by a remote after connecting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Handshake {
+	pub protocol: u32,
+	pub version: String,
+	///Features the remote needs from the daemon, currently one per command.
+	pub capabilities: Vec<String>,
+}
+
+///Sent back by the daemon if it is willing to serve the remote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandshakeAccepted {
+	pub protocol: u32,
+	pub version: String,
+}
+
+pub type HandshakeReply = Result<HandshakeAccepted, String>;
+
+impl Handshake {
+	pub fn new(capabilities: Vec<String>) -> Self {
+		Handshake {
+			protocol: PROTOCOL_VERSION,
+			version: BINARY_VERSION.to_string(),
+			capabilities,
+		}
+	}
+
+	///Decides whether a daemon built from this binary can serve the remote that sent `self`.
+	pub fn check(&self, supported: &[&str]) -> HandshakeReply {
+		if self.protocol != PROTOCOL_VERSION {
+			let (newer, restart) = if self.protocol > PROTOCOL_VERSION {
+				("remote", "Restart the daemon to pick up the new build.")
+			} else {
+				("daemon", "Use the remote from the same build as the running daemon.")
+			};
+			return Err(format!(
+				"Protocol mismatch: daemon speaks v{PROTOCOL_VERSION} (homehelper {BINARY_VERSION}), \
+				remote speaks v{} (homehelper {}). The {newer} is newer. {restart}",
+				self.protocol, self.version,
+			));
+		}
+
+		let missing: Vec<&str> = self.capabilities.iter()
+			.map(String::as_str)
+			.filter(|c| !supported.contains(c))
+			.collect();
+		if !missing.is_empty() {
+			return Err(format!(
+				"Daemon (homehelper {BINARY_VERSION}) does not support: {}. Restart the daemon to pick up the new build.",
+				missing.join(", "),
+			));
+		}
+
+		Ok(HandshakeAccepted {
+			protocol: PROTOCOL_VERSION,
+			version: BINARY_VERSION.to_string(),
+		})
+	}
+}
+
+///Turns the daemon's reply into an error the user can act on.
+pub fn expect_accepted(reply: Result<HandshakeReply, ciborium::de::Error<std::io::Error>>) -> Result<HandshakeAccepted> {
+	match reply {
+		Ok(Ok(accepted)) => Ok(accepted),
+		Ok(Err(reason)) => bail!(reason),
+		Err(ciborium::de::Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => bail!(
+			"Daemon closed the connection during the handshake. \
+			It is probably older than homehelper {BINARY_VERSION}; restart it to pick up the new build."
+		),
+		Err(e) => bail!("Could not read the daemon's handshake reply: {e}"),
+	}
+}
@@ -0,0 +1,151 @@
+use std::{
+	os::unix::process::CommandExt,
+	process::{Child, Command, Stdio},
+};
+
+use anyhow::{Context as _, Result};
+use regex::Regex;
+
+use crate::{config::{self, HookConfig}, hyprctl::Event, log_error, warn};
+
+use super::{Context, MainLoopStep, StepState, Supervision, TimerId};
+
+#[derive(Debug)]
+struct Running {
+	///With `command`, which `[[hooks]]` entry it was started for. Kept by value so that it still
+	///counts towards `max_concurrent` after a reload.
+	event: String,
+	command: String,
+	child: Child,
+	timeout: TimerId,
+}
+impl Running {
+	fn is(&self, hook: &HookConfig) -> bool {
+		self.event == hook.event && self.command == hook.command
+	}
+
+	///Kills the whole process group, so whatever `sh` started goes too.
+	fn kill(&mut self) {
+		if let Ok(pid) = i32::try_from(self.child.id()) {
+			//SAFETY: only sends a signal.
+			unsafe { libc::kill(-pid, libc::SIGKILL) };
+		}
+		let _ = self.child.wait();
+	}
+}
+
+fn matches(hook: &HookConfig, kind: &str, fields: &[(&str, String)]) -> bool {
+	//The first of `names` that the event has is matched against `pattern`.
+	let check = |pattern: &Option<Regex>, names: &[&str]| pattern.as_ref().is_none_or(|pattern| {
+		names.iter()
+			.find_map(|name| fields.iter().find(|(field, _)| field == name))
+			.is_some_and(|(_, value)| pattern.is_match(value))
+	});
+	(hook.event == "*" || hook.event == kind)
+		&& check(&hook.class, &["window_class"])
+		&& check(&hook.title, &["window_title"])
+		&& check(&hook.workspace, &["workspace_name", "workspace_id"])
+		&& check(&hook.monitor, &["monitor_name"])
+}
+
+fn spawn(hook: &HookConfig, kind: &str, fields: &[(&str, String)]) -> Result<Child> {
+	Command::new("sh")
+		.arg("-c")
+		.arg(&hook.command)
+		.env("HH_EVENT", kind)
+		.envs(fields.iter().map(|(name, value)| (format!("HH_{}", name.to_uppercase()), value)))
+		.stdin(Stdio::null())
+		.process_group(0)
+		.spawn()
+		.with_context(|| format!("Could not run hook `{}`", hook.command))
+}
+
+///Runs the `[[hooks]]` from the config file.
+#[derive(Debug, Default)]
+pub struct HooksStep {
+	running: Vec<Running>,
+} impl HooksStep {
+	///Forgets the hooks that have exited, reporting the ones that failed.
+	fn reap(&mut self, cx: &mut Context) {
+		self.running.retain_mut(|running| match running.child.try_wait() {
+			Ok(None) => true,
+			Ok(Some(status)) => {
+				if !status.success() {
+					warn!("Hook `{}` exited with {status}", running.command);
+				}
+				cx.timers.cancel(running.timeout);
+				false
+			},
+			Err(e) => {
+				warn!("Could not check on hook `{}`: {e}", running.command);
+				cx.timers.cancel(running.timeout);
+				false
+			},
+		});
+	}
+
+	fn kill_all(&mut self) {
+		for mut running in self.running.drain(..) {
+			running.kill();
+		}
+	}
+} impl MainLoopStep for HooksStep {
+	fn name(&self) -> &'static str {
+		"hooks"
+	}
+	fn supervision(&self) -> Supervision {
+		Supervision::Restart
+	}
+	//Woken up by SIGCHLD, among other things.
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.reap(cx);
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
+		let config = config::current();
+		if config.hooks.is_empty() {
+			return Ok(StepState::KeepActive);
+		}
+		self.reap(cx);
+
+		let kind = event.kind();
+		let fields = event.fields();
+		for hook in &config.hooks {
+			if !matches(hook, kind, &fields) {
+				continue;
+			}
+			let running = self.running.iter().filter(|r| r.is(hook)).count();
+			if running >= hook.max_concurrent {
+				warn!(event = kind; "Skipped hook `{}`: {running} already running", hook.command);
+				continue;
+			}
+			match spawn(hook, kind, &fields) {
+				Ok(child) => self.running.push(Running {
+					event: hook.event.clone(),
+					command: hook.command.clone(),
+					child,
+					timeout: cx.timers.after(hook.timeout()),
+				}),
+				Err(e) => log_error!(event = kind; &e),
+			}
+		}
+
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, _: &mut Context, timer: TimerId) -> Result<StepState> {
+		if let Some(i) = self.running.iter().position(|r| r.timeout == timer) {
+			let mut running = self.running.swap_remove(i);
+			warn!("Hook `{}` timed out; killing it", running.command);
+			running.kill();
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn restart(&mut self, _: &mut Context) -> Result<()> {
+		self.kill_all();
+		Ok(())
+	}
+} impl Drop for HooksStep {
+	fn drop(&mut self) {
+		self.kill_all();
+	}
+}
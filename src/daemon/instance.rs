@@ -0,0 +1,146 @@
+use std::{
+	ffi::OsString,
+	fs::{File, OpenOptions, TryLockError},
+	io::{self, Read, Seek, Write},
+	os::unix::net::UnixStream,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use super::{commands::{implementors::Quit, Command}, remote};
+
+///How long `--replace` waits for the old daemon to let go of the lock.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+///The socket and the lock, named after the Hyprland instance so that two sessions don't collide.
+///`HOMEHELPER_SOCKET` overrides the socket as it is, for sockets set up by systemd; the lock is then
+///`<socket>.lock`.
+fn paths(socket: Option<OsString>) -> Result<(PathBuf, PathBuf)> {
+	if let Some(socket) = socket {
+		let mut lock = socket.clone();
+		lock.push(".lock");
+		return Ok((socket.into(), lock.into()));
+	}
+	let runtime = std::env::var("XDG_RUNTIME_DIR").context("XDG_RUNTIME_DIR is not set")?;
+	let name = match std::env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+		Ok(his) if !his.is_empty() => format!("homehelper-{his}"),
+		_ => String::from("homehelper"),
+	};
+	let base = PathBuf::from(runtime).join(name);
+	Ok((base.with_extension("sock"), base.with_extension("lock")))
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+	Ok(paths(std::env::var_os("HOMEHELPER_SOCKET"))?.0)
+}
+
+fn lock_path() -> Result<PathBuf> {
+	Ok(paths(std::env::var_os("HOMEHELPER_SOCKET"))?.1)
+}
+
+///Held for as long as the daemon runs. The kernel drops the lock when the process dies, however
+///it dies, so a free lock means any socket file left behind is stale.
+#[derive(Debug)]
+pub struct InstanceLock {
+	_file: File,
+}
+impl InstanceLock {
+	///Takes the lock, asking the running daemon to quit first if `replace` is set.
+	pub fn acquire(replace: bool) -> Result<Self> {
+		let path = lock_path()?;
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)
+			.with_context(|| format!("Could not open lock file {}", path.display()))?;
+
+		match file.try_lock() {
+			Ok(()) => {},
+			Err(TryLockError::WouldBlock) if replace => {
+				let pid = read_pid(&mut file);
+				crate::info!("Asking the running daemon (pid {pid}) to quit");
+				request_quit().with_context(|| format!(
+					"Could not ask the running daemon (pid {pid}) to quit; kill it manually"
+				))?;
+				wait_for_lock(&file).with_context(|| format!(
+					"The running daemon (pid {pid}) did not quit within {REPLACE_TIMEOUT:?}; kill it manually"
+				))?;
+			},
+			Err(TryLockError::WouldBlock) => bail!(
+				"Another daemon (pid {}) is already running for this Hyprland instance. Pass --replace to take over.",
+				read_pid(&mut file),
+			),
+			Err(TryLockError::Error(e)) => return Err(e).context("Could not lock the instance lock file"),
+		}
+
+		file.set_len(0)?;
+		file.rewind()?;
+		write!(file, "{}", std::process::id())?;
+
+		Ok(InstanceLock { _file: file })
+	}
+}
+
+fn read_pid(file: &mut File) -> String {
+	let mut pid = String::new();
+	if file.rewind().is_err() || file.read_to_string(&mut pid).is_err() || pid.is_empty() {
+		return String::from("unknown");
+	}
+	pid
+}
+
+fn request_quit() -> Result<()> {
+	let mut socket = remote::request(&Command::Quit(Quit))?;
+	let (): () = crate::recv!(socket)?;
+	Ok(())
+}
+
+fn wait_for_lock(file: &File) -> Result<()> {
+	let start = Instant::now();
+	loop {
+		match file.try_lock() {
+			Ok(()) => return Ok(()),
+			Err(TryLockError::WouldBlock) if start.elapsed() < REPLACE_TIMEOUT => {
+				std::thread::sleep(Duration::from_millis(50));
+			},
+			Err(TryLockError::WouldBlock) => bail!("Timed out"),
+			Err(TryLockError::Error(e)) => return Err(e.into()),
+		}
+	}
+}
+
+///Must be called with the lock held, so nothing we started owns the socket. Something still
+///answering on it is a daemon that predates the lock file, which we leave alone.
+pub fn remove_stale_socket() -> Result<()> {
+	let path = socket_path()?;
+	match UnixStream::connect(&path) {
+		Ok(_) => bail!(
+			"{} is answering but not locked; an older daemon is probably still running",
+			path.display(),
+		),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+		Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+			crate::info!("Removing stale socket at {}", path.display());
+			std::fs::remove_file(&path).context("Could not remove stale socket")
+		},
+		Err(e) => Err(e).with_context(|| format!("Could not probe {}", path.display())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn socket_override_is_kept_as_is() {
+		for (socket, lock) in [("/tmp/hh", "/tmp/hh.lock"), ("/run/x.socket", "/run/x.socket.lock"), ("/run/hh.sock", "/run/hh.sock.lock")] {
+			let (socket_path, lock_path) = paths(Some(socket.into())).unwrap();
+			assert_eq!(socket_path, PathBuf::from(socket));
+			assert_eq!(lock_path, PathBuf::from(lock));
+		}
+	}
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+///What a workspace's name says about how bars show it: either a JSON `[index, icon, name]` triple,
+///or any other name, which is taken as the index. Empty parts are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceLabel {
+	///[`ID_INDEX`](Self::ID_INDEX) stands for the workspace's id.
+	pub index: String,
+	pub icon: String,
+	pub name: String,
+} impl WorkspaceLabel {
+	pub const ID_INDEX: &str = "#";
+
+	pub fn parse(name: &str) -> Self {
+		match serde_json::from_str::<[String; 3]>(name) {
+			Ok([index, icon, name]) => WorkspaceLabel { index, icon, name },
+			Err(_) => WorkspaceLabel { index: name.to_string(), ..Default::default() },
+		}
+	}
+
+	///The index to show for workspace `id`, if any.
+	pub fn index(&self, id: i32) -> Option<String> {
+		match self.index.as_str() {
+			"" => None,
+			Self::ID_INDEX => Some(id.to_string()),
+			index => Some(index.to_string()),
+		}
+	}
+
+	pub fn icon(&self) -> Option<&str> {
+		Some(self.icon.as_str()).filter(|icon| !icon.is_empty())
+	}
+
+	pub fn name(&self) -> Option<&str> {
+		Some(self.name.as_str()).filter(|name| !name.is_empty())
+	}
+}
+
+///The workspace name to set. A label with only an index is written as just that, unless it would
+///read back differently.
+impl fmt::Display for WorkspaceLabel {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let plain = self.icon.is_empty() && self.name.is_empty() && serde_json::from_str::<[String; 3]>(&self.index).is_err();
+		if plain {
+			return f.write_str(&self.index);
+		}
+		let triple = serde_json::to_string(&[&self.index, &self.icon, &self.name]).map_err(|_| fmt::Error)?;
+		f.write_str(&triple)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn label(index: &str, icon: &str, name: &str) -> WorkspaceLabel {
+		WorkspaceLabel { index: index.to_string(), icon: icon.to_string(), name: name.to_string() }
+	}
+
+	#[test]
+	fn round_trips() {
+		for label in [
+			label("web", "", ""),
+			label(WorkspaceLabel::ID_INDEX, "", ""),
+			label(WorkspaceLabel::ID_INDEX, "", "web"),
+			label("", "", ""),
+			label("", "", "web"),
+			label("1", "", ""),
+			label("", "", "[\"1\", \"\", \"web\"]"),
+			label("[\"1\", \"\", \"web\"]", "", ""),
+		] {
+			assert_eq!(WorkspaceLabel::parse(&label.to_string()), label, "{label}");
+		}
+	}
+
+	#[test]
+	fn plain_names_are_indices() {
+		assert_eq!(label("web", "", "").to_string(), "web");
+		assert_eq!(WorkspaceLabel::parse("#").index(3), Some(String::from("3")));
+		assert_eq!(label("[\"1\", \"\", \"web\"]", "", "").to_string(), r#"["[\"1\", \"\", \"web\"]","",""]"#);
+	}
+}
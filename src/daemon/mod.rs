@@ -1,5 +1,7 @@
 use std::{
-    os::unix::net::{UnixListener, UnixStream},
+    io::Read,
+    os::{fd::AsRawFd, unix::net::{UnixListener, UnixStream}},
+    path::PathBuf,
     process::Child,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -7,35 +9,96 @@ use std::{
     }, time::{Duration, Instant},
 };
 
-use crate::{hyprctl::{self, Event}, log_error};
-use anyhow::Result;
+use signal_hook::consts::{SIGCHLD, SIGHUP, SIGINT, SIGTERM};
+
+use crate::{config::{self, Config}, hyprctl::{self, Bind, Event}, info, log, log_error, warn};
+use anyhow::{bail, Result};
 
 mod submap;
 use submap::show_binds_in_submap;
 
 pub mod remote;
 pub mod commands;
+pub mod handshake;
+pub mod reply;
+use reply::{ErrorKind, RemoteError};
+pub mod client;
+use client::{Connection, Framing, Incoming};
+mod instance;
+use instance::InstanceLock;
+pub mod systemd;
+mod supervisor;
+pub use supervisor::{StepStatus, Supervision};
+use supervisor::{Outcome, StepSlot};
+mod timers;
+mod hooks;
+pub mod status;
+mod state;
+pub use state::{ActiveWindowState, HyprState, MonitorState, WindowState, WorkspaceState};
+mod label;
+pub use label::WorkspaceLabel;
+#[cfg(feature = "scripting")]
+mod scripting;
+#[cfg(feature = "dbus")]
+mod dbus;
+pub use timers::{TimerId, Timers};
 
 #[derive(Debug, Clone, clap::Args)]
 pub struct Arguments {
-    #[arg(short, long, action = clap::ArgAction::Set, default_value_t = true)]
-    submap: bool,
+    ///Overrides `submap.enabled` from the config file.
+    #[arg(short, long, action = clap::ArgAction::Set)]
+    submap: Option<bool>,
+    ///Defaults to `$XDG_CONFIG_HOME/homehelper/config.toml`
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    ///Ask an already running daemon to quit instead of refusing to start.
+    #[arg(long)]
+    replace: bool,
 }
 
-static DAEMON_SOCKET: LazyLock<String> = LazyLock::new(|| Daemon::socket_path().unwrap());
+static DAEMON_SOCKET: LazyLock<PathBuf> = LazyLock::new(|| instance::socket_path().unwrap());
 
 pub enum StepState {
 	KeepActive,
 	Done,
 }
 
+///What a step can reach besides itself while the main loop runs it.
+pub struct Context<'a> {
+	///This step's timers. They are dropped with the step, and when it is restarted.
+	pub timers: &'a mut Timers,
+	///What the daemon knows about Hyprland. Events that say enough are applied before steps see
+	///them; the others (like a workspace being created) only mark it stale, and it is queried again
+	///once the whole batch is handled, so it is only up to date again by the next `step`.
+	pub state: &'a HyprState,
+}
+
 ///A temporary, non-blocking stage in the main loop, often used for certain client requests.
 pub trait MainLoopStep: std::fmt::Debug {
-	fn step(&mut self) -> Result<StepState> {
+	///Short, kebab-case name shown in logs and `remote status`.
+	fn name(&self) -> &str;
+	///What to do when [`on_error`](Self::on_error) gives up.
+	fn supervision(&self) -> Supervision {
+		Supervision::Drop
+	}
+	///Called on every iteration of the main loop, which only wakes up for I/O and timers.
+	#[allow(unused_variables)]
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		Ok(StepState::KeepActive)
+	}
+	#[allow(unused_variables)]
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
 		Ok(StepState::KeepActive)
 	}
+	///Called after the daemon's config has been reloaded; [`config::current`] already returns the
+	///new one. Steps should drop anything they cached from Hyprland or the old config here.
 	#[allow(unused_variables)]
-	fn on_event(&mut self, event: &Event) -> Result<StepState> {
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		Ok(StepState::KeepActive)
+	}
+	///Called when a timer set through [`Context::timers`] is due.
+	#[allow(unused_variables)]
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
 		Ok(StepState::KeepActive)
 	}
 	///Used to catch errors, potentially to relay them to connected clients. Returns an Error if
@@ -44,26 +107,87 @@ pub trait MainLoopStep: std::fmt::Debug {
 	fn on_error(&mut self, error: anyhow::Error) -> Result<StepState> {
 		Err(error)
 	}
+	///Called before a step supervised with [`Supervision::Restart`] resumes after failing. Should
+	///drop whatever state might have caused the failure. Its timers have already been cancelled.
+	#[allow(unused_variables)]
+	fn restart(&mut self, cx: &mut Context) -> Result<()> {
+		Ok(())
+	}
 }
 
 #[derive(Debug)]
 struct SubmapContentEntry {
     panel: Option<Child>,
+	///Fetched on first use, dropped on reload.
+	binds: Option<Vec<Bind>>,
+	///The `--submap` argument, which wins over the config file.
+	forced: Option<bool>,
+	///Submap whose panel is waiting for `submap.delay_ms` to pass.
+	pending: Option<(String, TimerId)>,
+} impl SubmapContentEntry {
+	fn new(forced: Option<bool>) -> Self {
+		SubmapContentEntry { panel: None, binds: None, forced, pending: None }
+	}
+
+	fn close_panel(&mut self, cx: &mut Context) {
+		if let Some((_, timer)) = self.pending.take() {
+			cx.timers.cancel(timer);
+		}
+		if let Some(mut child) = self.panel.take() {
+			let _ = child.kill();
+		}
+	}
+
+	fn open_panel(&mut self, name: &str) -> Result<()> {
+		let binds = match &self.binds {
+			Some(binds) => binds,
+			None => self.binds.insert(hyprctl::binds()?),
+		};
+		self.panel = Some(show_binds_in_submap(binds, name)?);
+		Ok(())
+	}
 } impl MainLoopStep for SubmapContentEntry {
-	fn on_event(&mut self, event: &Event) -> Result<StepState> {
+	fn name(&self) -> &'static str {
+		"submap"
+	}
+	fn supervision(&self) -> Supervision {
+		Supervision::Restart
+	}
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
         if let Event::Submap { name } = event {
+			self.close_panel(cx);
 			if let Some(name) = name {
-				if let Some(mut child) = self.panel.take() {
-					let _ = child.kill();
+				let delay = config::current().submap.delay();
+				if delay.is_zero() {
+					self.open_panel(name)?;
+				} else {
+					self.pending = Some((name.clone(), cx.timers.after(delay)));
 				}
-				self.panel = Some(show_binds_in_submap(name)?);
-			} else if let Some(mut child) = self.panel.take() {
-				let _ = child.kill();
 			}
 		}
 
 		Ok(StepState::KeepActive)
 	}
+	fn on_timer(&mut self, _: &mut Context, timer: TimerId) -> Result<StepState> {
+		if let Some((name, _)) = self.pending.take_if(|(_, pending)| *pending == timer) {
+			self.open_panel(&name)?;
+		}
+		Ok(StepState::KeepActive)
+	}
+	fn on_reload(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.binds = None;
+		if self.forced.unwrap_or(config::current().submap.enabled) {
+			Ok(StepState::KeepActive)
+		} else {
+			self.close_panel(cx);
+			Ok(StepState::Done)
+		}
+	}
+	fn restart(&mut self, cx: &mut Context) -> Result<()> {
+		self.close_panel(cx);
+		self.binds = None;
+		Ok(())
+	}
 }
 
 #[derive(Debug)]
@@ -71,53 +195,140 @@ pub struct Daemon {
     options: Arguments,
     socket2: hyprctl::Socket2,
     home_helper_socket: UnixListener,
-	pub steps: Vec<Box<dyn MainLoopStep>>
+	///False if systemd created the socket, in which case it also cleans it up.
+	owns_socket: bool,
+	connections: Vec<Connection>,
+	next_client_id: u64,
+	///Their [`Connection::deadline`]s.
+	connection_timers: Timers,
+	must_exit: Arc<AtomicBool>,
+	must_reload: Arc<AtomicBool>,
+	///Readable once a signal arrived, so that one coming in just before `poll` isn't missed, or
+	///once a record was logged, for `logs --follow`.
+	signal_pipe: UnixStream,
+	steps: Vec<StepSlot>,
+	next_step_id: u64,
+	started: Instant,
+	events: status::EventStats,
+	state: HyprState,
+	///Window `focus-urgent` went to last, so that calling it again moves on to the next one.
+	last_urgent_focus: Option<hyprctl::WindowAddress>,
+	///Set when a step supervised with [`Supervision::Escalate`] fails; stops the daemon.
+	escalated: Option<anyhow::Error>,
+	//Declared last so it is released only after `drop` has removed the socket.
+	_lock: InstanceLock,
 } impl Daemon {
     fn new(options: Arguments) -> Result<Self> {
+		let lock = InstanceLock::acquire(options.replace)?;
         let addr = &*DAEMON_SOCKET;
-        println!("Opening socket at {addr}");
-        let socket = UnixListener::bind(addr)?;
-        socket.set_nonblocking(true)?;
+		let (socket, owns_socket) = if let Some(socket) = systemd::listen_fds()? {
+			info!("Using socket from systemd");
+			(socket, false)
+		} else {
+			instance::remove_stale_socket()?;
+			info!("Opening socket at {}", addr.display());
+			let socket = UnixListener::bind(addr)?;
+			socket.set_nonblocking(true)?;
+			(socket, true)
+		};
 
         let socket2 = hyprctl::Socket2::new()?;
-		let steps: Vec<Box<dyn MainLoopStep>> = vec![];
+
+		let must_exit = Arc::new(AtomicBool::new(false));
+		let must_reload = Arc::new(AtomicBool::new(false));
+		for signal in [SIGINT, SIGTERM] {
+			signal_hook::flag::register(signal, Arc::clone(&must_exit))?;
+		}
+		signal_hook::flag::register(SIGHUP, Arc::clone(&must_reload))?;
+		//Registered after the flags, so they are set by the time `poll` sees the byte. SIGCHLD
+		//only wakes the loop up, letting steps reap their children.
+		let (signal_pipe, signal_writer) = UnixStream::pair()?;
+		signal_pipe.set_nonblocking(true)?;
+		signal_writer.set_nonblocking(true)?;
+		for signal in [SIGINT, SIGTERM, SIGHUP, SIGCHLD] {
+			signal_hook::low_level::pipe::register(signal, signal_writer.try_clone()?)?;
+		}
+		log::wake_on_write(signal_writer);
+
         Ok(Daemon {
             options,
             socket2,
             home_helper_socket: socket,
-			steps,
+			owns_socket,
+			connections: vec![],
+			next_client_id: 0,
+			connection_timers: Timers::default(),
+			must_exit,
+			must_reload,
+			signal_pipe,
+			steps: vec![],
+			next_step_id: 0,
+			started: Instant::now(),
+			events: status::EventStats::default(),
+			state: HyprState::new(),
+			last_urgent_focus: None,
+			escalated: None,
+			_lock: lock,
         })
     }
-    pub fn socket_path() -> Result<String> {
-        let runtime = std::env::var("XDG_RUNTIME_DIR")?;
-        Ok(format!("{runtime}/homehelper.sock"))
-    }
+
+	///Makes the main loop exit after the current iteration.
+	pub fn request_exit(&self) {
+		self.must_exit.store(true, Ordering::Relaxed);
+	}
+
+	///Adds a step to the main loop, supervised according to its [`MainLoopStep::supervision`].
+	pub fn add_step(&mut self, step: impl MainLoopStep + 'static) {
+		self.next_step_id += 1;
+		self.steps.push(StepSlot::new(self.next_step_id, Box::new(step)));
+	}
+
+	#[cfg(feature = "scripting")]
+	pub fn has_step(&self, name: &str) -> bool {
+		self.steps.iter().any(|step| step.step_name() == name)
+	}
+
+	pub fn step_statuses(&self) -> Vec<StepStatus> {
+		self.steps.iter().map(StepSlot::status).collect()
+	}
 
     pub fn launch(options: Arguments) -> Result<()> {
+		config::set(Config::load_or_default(options.config.as_deref())?);
         let mut d = Self::new(options)?;
-		if d.options.submap {
-			d.steps.push(Box::new(SubmapContentEntry { panel: None }));
+		if d.submap_enabled() {
+			d.add_step(SubmapContentEntry::new(d.options.submap));
 		}
+		d.add_step(hooks::HooksStep::default());
+		#[cfg(feature = "scripting")]
+		scripting::load_all(&mut d);
+		#[cfg(feature = "dbus")]
+		dbus::start(&mut d);
 
-        let must_exit = Arc::new(AtomicBool::new(false));
-        let thread_must_exit = Arc::clone(&must_exit);
-
-        ctrlc::set_handler(move || {
-            thread_must_exit.store(true, Ordering::Relaxed);
-        })?;
+		systemd::notify("READY=1");
+		let mut watchdog = systemd::Watchdog::from_env();
 
-		let mut last_step = Instant::now();
         loop {
             d.step()?;
-            if must_exit.load(Ordering::Relaxed) {
+			if let Some(e) = d.escalated.take() {
+				return Err(e);
+			}
+            if d.must_exit.load(Ordering::Relaxed) {
                 break;
             }
-			let this_step = Instant::now();
-			if this_step.duration_since(last_step) < Duration::from_millis(25) {
-				std::thread::sleep(Duration::from_millis(25) - this_step.duration_since(last_step));
+			if d.must_reload.swap(false, Ordering::Relaxed) && let Err(e) = d.reload() {
+				log_error!(&e);
+			}
+			if let Some(watchdog) = &mut watchdog {
+				watchdog.ping_if_due();
 			}
-			last_step = this_step;
+			let deadline = d.steps.iter()
+				.filter_map(StepSlot::next_deadline)
+				.chain(d.connection_timers.next_deadline())
+				.chain(watchdog.as_ref().map(systemd::Watchdog::next_ping))
+				.min();
+			d.wait(deadline)?;
         }
+		systemd::notify("STOPPING=1");
 
         Ok(())
     }
@@ -125,108 +336,243 @@ pub struct Daemon {
     fn step(&mut self) -> Result<()> {
         self.hyprctl_step()?;
         self.listener_step()?;
+		let now = Instant::now();
+		self.supervise(|slot, state| slot.run_timers(state, now));
+		self.run_steps(None, |step, cx| step.step(cx));
+		self.flush_connections();
+
+        Ok(())
+    }
+
+	///Runs `f` on every step under supervision, removing the ones that finish or are dropped.
+	///`event` is the kind of event being handled, for the logs.
+	fn run_steps(&mut self, event: Option<&str>, mut f: impl FnMut(&mut dyn MainLoopStep, &mut Context) -> Result<StepState>) {
+		self.supervise(|slot, state| slot.run(state, event, &mut f));
+	}
+
+	fn supervise(&mut self, mut f: impl FnMut(&mut StepSlot, &HyprState) -> Outcome) {
 		let mut i = 0;
 		while i < self.steps.len() {
-			let entry = &mut self.steps[i];
-			let mut should_increment = true;
-			match entry.step() {
-				Ok(StepState::Done) => {
+			match f(&mut self.steps[i], &self.state) {
+				Outcome::Keep => i += 1,
+				Outcome::Remove => {
 					self.steps.remove(i);
-					should_increment = false;
 				},
-				Ok(StepState::KeepActive) => {},
-				Err(e) => {
-					match entry.on_error(e) {
-						Ok(StepState::KeepActive) => {},
-						Ok(StepState::Done) => {
-							self.steps.remove(i);
-							should_increment = false;
-						}
-						Err(e) => {
-							self.steps.remove(i);
-							return Err(e);
-						}
-					}
-				}
-			}
-			if should_increment {
-				i += 1;
+				Outcome::Escalate(e) => {
+					self.steps.remove(i);
+					self.escalated.get_or_insert(e);
+				},
 			}
 		}
+	}
 
-        Ok(())
-    }
+	fn submap_enabled(&self) -> bool {
+		self.options.submap.unwrap_or(config::current().submap.enabled)
+	}
+
+	///Re-reads the config file and lets every step react to it. Keeps the old config if the new
+	///one is invalid.
+	pub fn reload(&mut self) -> Result<()> {
+		let was_submap_enabled = self.submap_enabled();
+		#[cfg(feature = "dbus")]
+		let was_dbus_enabled = config::current().dbus.enabled;
+		config::set(Config::load_or_default(self.options.config.as_deref())?);
+		info!("Reloaded config");
+
+		self.supervise(StepSlot::reload);
+		if !was_submap_enabled && self.submap_enabled() {
+			self.add_step(SubmapContentEntry::new(self.options.submap));
+		}
+		//Scripts that changed ended themselves in `on_reload`.
+		#[cfg(feature = "scripting")]
+		scripting::load_all(self);
+		#[cfg(feature = "dbus")]
+		if !was_dbus_enabled {
+			dbus::start(self);
+		}
+
+		Ok(())
+	}
 
-    #[allow(clippy::unnecessary_wraps)]
     fn hyprctl_step(&mut self) -> Result<()> {
         let events: Vec<Result<Event>> = (&mut self.socket2).collect();
 
         for event in events {
-            match self.handle_event(event) {
-                Ok(()) => {}
-                Err(e) => log_error(&e),
+            match event {
+                Ok(event) => self.handle_event(&event),
+                Err(e) => log_error!(&e),
             }
         }
+		if self.socket2.is_closed() {
+			bail!("Hyprland closed its event socket");
+		}
+		if let Err(e) = self.state.sync_if_stale() {
+			log_error!(&e.context("Could not query Hyprland's workspaces and monitors"));
+		}
 
         Ok(())
     }
 
-	fn listener_handle_socket(&mut self, mut socket: UnixStream) -> Result<()> {
-		let request: commands::Command = ciborium::from_reader(&mut socket)?;
-		request.dispatch_daemon(self, socket)?;
+	///Blocks until Hyprland sends an event, a remote connects or sends something, queued output
+	///can be written, a signal arrives, or `deadline` passes.
+	fn wait(&mut self, deadline: Option<Instant>) -> Result<()> {
+		if self.socket2.has_buffered() {
+			return Ok(());
+		}
+		let pollfd = |fd: i32, events: libc::c_short| libc::pollfd { fd, events, revents: 0 };
+		let mut fds = vec![
+			pollfd(self.socket2.as_raw_fd(), libc::POLLIN),
+			pollfd(self.home_helper_socket.as_raw_fd(), libc::POLLIN),
+			pollfd(self.signal_pipe.as_raw_fd(), libc::POLLIN),
+		];
+		fds.extend(self.connections.iter().map(|c| pollfd(c.as_raw_fd(), c.poll_events())));
+
+		//Rounded up, so the deadline has passed when we wake up.
+		let timeout = deadline.map_or(-1, |deadline| {
+			let left = deadline.saturating_duration_since(Instant::now()) + Duration::from_nanos(999_999);
+			i32::try_from(left.as_millis()).unwrap_or(i32::MAX)
+		});
+		//SAFETY: `fds` is a valid array of `fds.len()` pollfds.
+		let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+		if ready < 0 {
+			let e = std::io::Error::last_os_error();
+			//A signal, most likely one of ours; the main loop checks its flags next.
+			if e.kind() == std::io::ErrorKind::Interrupted {
+				return Ok(());
+			}
+			return Err(e.into());
+		}
 
+		if fds[2].revents & libc::POLLIN != 0 {
+			//The main loop checks the flags next; the bytes only woke it up.
+			let mut buffer = [0; 64];
+			while matches!(self.signal_pipe.read(&mut buffer), Ok(n) if n > 0) {}
+		}
+		for (connection, fd) in self.connections.iter_mut().zip(&fds[3..]) {
+			if fd.revents & (libc::POLLHUP | libc::POLLERR) != 0 {
+				connection.hang_up();
+			}
+		}
 		Ok(())
 	}
 
-    fn listener_step(&mut self) -> Result<()> {
-        match self.home_helper_socket.accept() {
-            Ok((s, _)) => {
-				match self.listener_handle_socket(s) {
-					Ok(()) => {},
-					Err(e) => log_error(&e),
+	fn listener_handle_frame(&mut self, connection: &mut Connection, frame: Incoming) -> Result<()> {
+		match frame {
+			Incoming::Handshake(handshake) => {
+				let reply = handshake.check(commands::Command::CAPABILITIES);
+				connection.client().send_frame(&reply)?;
+				if let Err(reason) = reply {
+					connection.finish();
+					//The remote was told; it's up to its user to act on it.
+					warn!("Refused remote {}: {reason}", connection.client().id());
 				}
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-            Err(e) => Err(e)?,
-        }
-        Ok(())
-    }
-
-    fn handle_event(&mut self, event: Result<Event>) -> Result<()> {
-		let event = event?;
-		let mut i = 0;
-		while i < self.steps.len() {
-			let entry = &mut self.steps[i];
-			let mut should_increment = true;
-			match entry.on_event(&event) {
-				Ok(StepState::KeepActive) => {},
-				Ok(StepState::Done) => {
-					self.steps.remove(i);
-					should_increment = false;
-				},
-				Err(e) => match entry.on_error(e) {
-					Ok(StepState::KeepActive) => {},
-					Ok(StepState::Done) => {
-						self.steps.remove(i);
-						should_increment = false;
-					}
-					Err(e) => {
-						self.steps.remove(i);
+			},
+			Incoming::Command(request) => {
+				let client = connection.client().clone();
+				client.set_command(request.capability());
+				if let Err(e) = request.dispatch_daemon(self, client.clone()) {
+					let _ = client.send_error(&e);
+					//Only the daemon's own failures are worth a notification; the remote reports
+					//the rest.
+					let error = RemoteError::from(&e);
+					if !matches!(error.kind, ErrorKind::Request | ErrorKind::Protocol) {
 						return Err(e);
 					}
+					info!("Refused remote {}'s request ({}): {}", client.id(), error.kind, error.chain.join(": "));
 				}
+			},
+		}
+
+		Ok(())
+	}
+
+	fn listener_handle_connection(&mut self, connection: &mut Connection) -> Result<()> {
+		connection.fill()?;
+		loop {
+			match connection.next_frame() {
+				Ok(Some(frame)) => self.listener_handle_frame(connection, frame)?,
+				Ok(None) => break,
+				Err(e) => {
+					//JSON was likely written by hand rather than sent by another build.
+					let e = match connection.framing() {
+						Some(Framing::Cbor) => e.context("Malformed request (is the remote older than the daemon?)"),
+						_ => e.context("Malformed request"),
+					};
+					let _ = connection.client().send_error(&RemoteError::new(ErrorKind::Protocol, &e).into());
+					connection.finish();
+					warn!("Remote {}: {e:#}", connection.client().id());
+					break;
+				},
 			}
-			if should_increment {
-				i += 1;
+		}
+
+		Ok(())
+	}
+
+    fn listener_step(&mut self) -> Result<()> {
+		loop {
+			match self.home_helper_socket.accept() {
+				Ok((s, _)) => {
+					self.next_client_id += 1;
+					let mut connection = Connection::new(s, self.next_client_id)?;
+					connection.set_deadline(self.connection_timers.after(client::REQUEST_TIMEOUT));
+					self.connections.push(connection);
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+				Err(e) => Err(e)?,
 			}
 		}
 
+		for timer in self.connection_timers.take_due(Instant::now()) {
+			if let Some(connection) = self.connections.iter_mut().find(|c| c.deadline() == Some(timer))
+				&& connection.time_out()
+			{
+				warn!("Dropped remote {}: no request within {:?}", connection.client().id(), client::REQUEST_TIMEOUT);
+			}
+		}
+
+		//Handlers get the whole daemon, so each connection is taken out while it is handled. The
+		//others stay put for commands like `status`.
+		for i in 0..self.connections.len() {
+			let mut connection = self.connections.remove(i);
+			if let Err(e) = self.listener_handle_connection(&mut connection) {
+				log_error!(&e);
+			}
+			self.connections.insert(i, connection);
+		}
+
         Ok(())
     }
+
+	fn flush_connections(&mut self) {
+		for connection in &mut self.connections {
+			match connection.flush() {
+				Ok(()) => {},
+				//The remote went away; the connection is closed below.
+				Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| matches!(
+					e.kind(),
+					std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+				)) => {},
+				Err(e) => log_error!(&e),
+			}
+		}
+		self.connections.retain(|c| !c.is_finished());
+	}
+
+    fn handle_event(&mut self, event: &Event) {
+		self.events.record(event.kind());
+		self.state.apply(event);
+		if let Event::ConfigReloaded = event && let Err(e) = self.reload() {
+			log_error!(event = event.kind(); &e);
+		}
+		self.run_steps(Some(event.kind()), |step, cx| step.on_event(cx, event));
+    }
 } impl Drop for Daemon {
     fn drop(&mut self) {
-        let addr = &*DAEMON_SOCKET;
-        println!("Closing socket at {addr}");
-        let _ = std::fs::remove_file(addr);
+        if self.owns_socket {
+			let addr = &*DAEMON_SOCKET;
+			info!("Closing socket at {}", addr.display());
+			let _ = std::fs::remove_file(addr);
+		}
     }
 }
@@ -1,4 +1,4 @@
-use super::DAEMON_SOCKET;
+use super::{commands::Command, handshake::{self, Handshake}, DAEMON_SOCKET};
 use anyhow::{bail, Result};
 use std::{io, os::unix::net::UnixStream};
 
@@ -8,19 +8,31 @@ fn connect() -> Result<UnixStream> {
 		Err(e) if e.kind() == io::ErrorKind::NotFound => {
 			bail!("Socket file not found. Is the daemon running?");
 		},
+		Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+			bail!("Nothing is listening on {}. The daemon probably crashed; start it again.", DAEMON_SOCKET.display());
+		},
 		Err(e) => bail!(e),
 	}
 }
 
-pub fn launch(arguments: Arguments) -> Result<()> {
+///Connects to the daemon and sends `command`, leaving the reply for the caller to read.
+pub fn request(command: &Command) -> Result<UnixStream> {
 	let mut socket = connect()?;
-	ciborium::into_writer(&arguments.command, &mut socket)?;
+	ciborium::into_writer(&Handshake::new(vec![command.capability().to_string()]), &mut socket)?;
+	handshake::expect_accepted(ciborium::from_reader(&mut socket))?;
+
+	ciborium::into_writer(command, &mut socket)?;
+	Ok(socket)
+}
+
+pub fn launch(arguments: Arguments) -> Result<()> {
+	let socket = request(&arguments.command)?;
 	arguments.command.dispatch_remote(socket)?;
     Ok(())
 }
 
 #[derive(Debug, Clone, clap::Args)]
 pub struct Arguments {
-    #[arg(value_enum)]
-    command: super::commands::Command,
+    #[command(subcommand)]
+    command: Command,
 }
@@ -0,0 +1,106 @@
+use std::{fmt::Display, io};
+
+use anyhow::{bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::hyprctl;
+
+///Envelope around every frame the daemon sends after the handshake.
+pub type Reply<T> = Result<T, RemoteError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+	///The two ends disagree about what is being sent.
+	Protocol,
+	///The request itself can't be served.
+	Request,
+	///Hyprland could not be reached or refused a request.
+	Hyprland,
+	Io,
+	Internal,
+} impl ErrorKind {
+	///Exit status for `homehelper remote`, following sysexits.h.
+	pub fn exit_code(self) -> i32 {
+		match self {
+			ErrorKind::Request => 64,  //EX_USAGE
+			ErrorKind::Hyprland => 69, //EX_UNAVAILABLE
+			ErrorKind::Internal => 70, //EX_SOFTWARE
+			ErrorKind::Io => 74,       //EX_IOERR
+			ErrorKind::Protocol => 76, //EX_PROTOCOL
+		}
+	}
+} impl Display for ErrorKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ErrorKind::Protocol => "protocol error",
+			ErrorKind::Request => "bad request",
+			ErrorKind::Hyprland => "hyprland error",
+			ErrorKind::Io => "i/o error",
+			ErrorKind::Internal => "internal error",
+		})
+	}
+}
+
+///An error that happened inside the daemon, as seen by the remote.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+pub struct RemoteError {
+	pub kind: ErrorKind,
+	///Outermost message first, like `{:#}` on an `anyhow::Error`.
+	pub chain: Vec<String>,
+} impl RemoteError {
+	pub fn new(kind: ErrorKind, error: &anyhow::Error) -> Self {
+		RemoteError {
+			kind,
+			chain: error.chain()
+				.flat_map(|cause| match cause.downcast_ref::<RemoteError>() {
+					Some(e) => e.chain.clone(),
+					None => vec![cause.to_string()],
+				})
+				.collect(),
+		}
+	}
+
+	///The remote asked for something that can't be done, like renaming a workspace that doesn't
+	///exist.
+	pub fn request(message: impl Display) -> anyhow::Error {
+		RemoteError { kind: ErrorKind::Request, chain: vec![message.to_string()] }.into()
+	}
+} impl Display for RemoteError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Daemon reported {}: {}", self.kind, self.chain.join(": "))
+	}
+} impl From<&anyhow::Error> for RemoteError {
+	fn from(error: &anyhow::Error) -> Self {
+		//`downcast_ref` looks through every layer of context, unlike `chain`.
+		let kind = if let Some(e) = error.downcast_ref::<RemoteError>() {
+			e.kind
+		} else if error.downcast_ref::<hyprctl::Error>().is_some() || error.downcast_ref::<serde_json::Error>().is_some() {
+			ErrorKind::Hyprland
+		} else if error.downcast_ref::<ciborium::de::Error<std::io::Error>>().is_some()
+			|| error.downcast_ref::<ciborium::ser::Error<std::io::Error>>().is_some()
+		{
+			ErrorKind::Protocol
+		} else if error.downcast_ref::<std::io::Error>().is_some() {
+			ErrorKind::Io
+		} else {
+			ErrorKind::Internal
+		};
+		RemoteError::new(kind, error)
+	}
+}
+
+///Exit status for an error that ended `homehelper remote`.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+	error.downcast_ref::<RemoteError>().map_or(1, |e| e.kind.exit_code())
+}
+
+///Remote side of the envelope, see [`recv`](crate::recv).
+pub fn receive<T: DeserializeOwned>(socket: &mut impl io::Read) -> Result<T> {
+	match ciborium::from_reader::<Reply<T>, _>(socket) {
+		Ok(reply) => Ok(reply?),
+		Err(ciborium::de::Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+			bail!("Daemon closed the connection without replying")
+		},
+		Err(e) => Err(e.into()),
+	}
+}
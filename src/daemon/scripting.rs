@@ -0,0 +1,391 @@
+use std::{
+	cell::RefCell,
+	collections::HashMap,
+	path::{Path, PathBuf},
+	rc::Rc,
+	time::Duration,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST};
+use serde::Serialize;
+
+use crate::{config::{self, ScriptingConfig}, debug, hyprctl::{self, Event}, info, log_error};
+
+use super::{Context, Daemon, HyprState, MainLoopStep, StepState, Supervision, TimerId};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+///Anything shorter would keep the daemon busy for nothing.
+const MIN_INTERVAL_MS: i64 = 50;
+
+///What `workspaces()` returns for each workspace.
+#[derive(Debug, Serialize)]
+struct ScriptWorkspace<'a> {
+	id: i32,
+	name: &'a str,
+	monitor: &'a str,
+	windows: usize,
+	has_fullscreen: bool,
+}
+
+///What `monitors()` returns for each monitor. `special_workspace` is empty unless one is shown.
+#[derive(Debug, Serialize)]
+struct ScriptMonitor<'a> {
+	id: i32,
+	name: &'a str,
+	description: &'a str,
+	width: u32,
+	height: u32,
+	scale: f32,
+	active_workspace: i32,
+	special_workspace: &'a str,
+	focused: bool,
+	enabled: bool,
+}
+
+///The daemon's state as scripts see it, taken before each call into them so that they don't have
+///to ask Hyprland.
+#[derive(Debug, Default)]
+struct StateSnapshot {
+	///[`HyprState::generation`] it was taken at.
+	generation: Option<u64>,
+	monitors: Dynamic,
+	workspaces: Dynamic,
+} impl StateSnapshot {
+	fn update(&mut self, state: &HyprState) -> Result<()> {
+		if self.generation == Some(state.generation()) {
+			return Ok(());
+		}
+		let workspaces: Vec<ScriptWorkspace> = state.workspaces().iter()
+			.map(|workspace| {
+				let windows = || state.windows().iter().filter(|w| w.workspace_id == workspace.id);
+				ScriptWorkspace {
+					id: workspace.id,
+					name: &workspace.name,
+					monitor: &workspace.monitor,
+					windows: windows().count(),
+					has_fullscreen: windows().any(|w| w.fullscreen),
+				}
+			})
+			.collect();
+		let monitors: Vec<ScriptMonitor> = state.monitors().iter()
+			.map(|monitor| ScriptMonitor {
+				id: monitor.id,
+				name: &monitor.name,
+				description: &monitor.description,
+				width: monitor.width,
+				height: monitor.height,
+				scale: monitor.scale,
+				active_workspace: monitor.active_workspace,
+				special_workspace: monitor.special_workspace
+					.and_then(|id| state.workspaces().iter().find(|w| w.id == id))
+					.map_or("", |w| w.name.as_str()),
+				focused: monitor.focused,
+				enabled: monitor.enabled,
+			})
+			.collect();
+		self.workspaces = rhai::serde::to_dynamic(workspaces).map_err(|e| anyhow!("{e}"))?;
+		self.monitors = rhai::serde::to_dynamic(monitors).map_err(|e| anyhow!("{e}"))?;
+		self.generation = Some(state.generation());
+		Ok(())
+	}
+}
+
+///Timer calls made by a script, applied to the step's [`Timers`](super::Timers) once the script
+///returns. Scripts only ever see their own ids.
+#[derive(Debug, Default)]
+struct ScriptTimers {
+	next_id: i64,
+	requests: Vec<TimerRequest>,
+}
+
+#[derive(Debug)]
+enum TimerRequest {
+	After(i64, Duration),
+	Every(i64, Duration),
+	Cancel(i64),
+}
+
+fn duration(ms: i64) -> Duration {
+	Duration::from_millis(u64::try_from(ms).unwrap_or(0))
+}
+
+///Turns our errors into ones a script can catch.
+#[allow(clippy::unnecessary_box_returns)] //Rhai wants them boxed.
+fn script_error(error: &anyhow::Error) -> Box<EvalAltResult> {
+	format!("{error:#}").into()
+}
+
+fn engine(name: &str, timers: &Rc<RefCell<ScriptTimers>>, snapshot: &Rc<RefCell<StateSnapshot>>) -> Engine {
+	let mut engine = Engine::new();
+	engine.set_max_operations(config::current().scripting.max_operations);
+
+	let print_name = name.to_string();
+	engine.on_print(move |message| info!(step = &print_name; "{message}"));
+	let debug_name = name.to_string();
+	engine.on_debug(move |message, _, position| debug!(step = &debug_name; "{position}: {message}"));
+
+	let monitors = Rc::clone(snapshot);
+	engine.register_fn("monitors", move || monitors.borrow().monitors.clone());
+	let workspaces = Rc::clone(snapshot);
+	engine.register_fn("workspaces", move || workspaces.borrow().workspaces.clone());
+	engine.register_fn("dispatch", |dispatcher: &str, args: &str| -> ScriptResult<()> {
+		hyprctl::dispatch(dispatcher, args).map_err(|e| script_error(&e))
+	});
+	engine.register_fn("dispatch", |dispatcher: &str| -> ScriptResult<()> {
+		hyprctl::dispatch(dispatcher, "").map_err(|e| script_error(&e))
+	});
+	engine.register_fn("keyword", |keyword: &str, value: Dynamic| -> ScriptResult<()> {
+		hyprctl::keyword(keyword, &value.to_string()).map_err(|e| script_error(&e))
+	});
+	engine.register_fn("notify", |message: &str| -> ScriptResult<()> {
+		notify(message, 5000)
+	});
+	engine.register_fn("notify", notify);
+	engine.register_fn("dismiss_notify", || -> ScriptResult<()> {
+		hyprctl::dismiss_notify(None).map_err(|e| script_error(&e))
+	});
+
+	let after = Rc::clone(timers);
+	engine.register_fn("after", move |ms: i64| -> i64 {
+		let mut timers = after.borrow_mut();
+		timers.next_id += 1;
+		let id = timers.next_id;
+		timers.requests.push(TimerRequest::After(id, duration(ms)));
+		id
+	});
+	let every = Rc::clone(timers);
+	engine.register_fn("every", move |ms: i64| -> ScriptResult<i64> {
+		if ms < MIN_INTERVAL_MS {
+			return Err(format!("every({ms}): the interval must be at least {MIN_INTERVAL_MS} ms").into());
+		}
+		let mut timers = every.borrow_mut();
+		timers.next_id += 1;
+		let id = timers.next_id;
+		timers.requests.push(TimerRequest::Every(id, duration(ms)));
+		Ok(id)
+	});
+	let cancel = Rc::clone(timers);
+	engine.register_fn("cancel", move |id: i64| {
+		cancel.borrow_mut().requests.push(TimerRequest::Cancel(id));
+	});
+
+	engine
+}
+
+fn notify(message: &str, duration_ms: i64) -> ScriptResult<()> {
+	hyprctl::send_notification(&hyprctl::Notification {
+		icon: hyprctl::NotifyIcon::Info,
+		time: duration(duration_ms),
+		color: None,
+		font_size: None,
+		message: message.to_string(),
+	}).map_err(|e| script_error(&e))
+}
+
+fn event_map(event: &Event) -> Map {
+	let mut map = Map::new();
+	map.insert("kind".into(), event.kind().into());
+	for (name, value) in event.fields() {
+		map.insert(name.into(), value.into());
+	}
+	map
+}
+
+///One script file. Its functions are optional:
+///- `on_load()` runs once the script is loaded (and again after a restart).
+///- `on_event(event)` gets every Hyprland event as a map with a `kind` and the event's fields.
+///- `on_timer(id)` runs when a timer from `after(ms)` or `every(ms)` fires.
+///
+///All of them can keep state in `this`, which starts out as an empty map.
+pub struct ScriptStep {
+	path: PathBuf,
+	///`script:<file name>`
+	name: String,
+	///What `ast` was compiled from, and under which config; the script is only loaded again on
+	///reload if either changed.
+	source: String,
+	config: ScriptingConfig,
+	engine: Engine,
+	ast: AST,
+	this: Dynamic,
+	///Whether `on_load` has run since the script was (re)started.
+	loaded: bool,
+	timers: Rc<RefCell<ScriptTimers>>,
+	snapshot: Rc<RefCell<StateSnapshot>>,
+	///Script timer id to ours, and whether it repeats.
+	active: HashMap<i64, (TimerId, bool)>,
+} impl ScriptStep {
+	pub fn load(path: &Path) -> Result<Self> {
+		let name = Self::name_for(path);
+		let timers = Rc::default();
+		let snapshot = Rc::default();
+		let engine = engine(&name, &timers, &snapshot);
+		let source = Self::read(path)?;
+		let ast = Self::compile(&engine, path, &source)?;
+		Ok(ScriptStep {
+			path: path.to_path_buf(),
+			name,
+			source,
+			config: config::current().scripting.clone(),
+			engine,
+			ast,
+			this: Map::new().into(),
+			loaded: false,
+			timers,
+			snapshot,
+			active: HashMap::new(),
+		})
+	}
+
+	fn name_for(path: &Path) -> String {
+		format!("script:{}", path.file_name().unwrap_or_default().to_string_lossy())
+	}
+
+	fn read(path: &Path) -> Result<String> {
+		std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))
+	}
+
+	fn compile(engine: &Engine, path: &Path, source: &str) -> Result<AST> {
+		engine.compile(source)
+			.map_err(|e| anyhow!("{e}"))
+			.with_context(|| format!("Could not load {}", path.display()))
+	}
+
+	///Calls `function` if the script defines it, then applies whatever it did to its timers.
+	fn call(&mut self, cx: &mut Context, function: &str, args: impl FuncArgs) -> Result<()> {
+		if !self.ast.iter_functions().any(|f| f.name == function) {
+			return Ok(());
+		}
+		self.snapshot.borrow_mut().update(cx.state)?;
+		let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.this);
+		let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, function, args);
+		self.apply_timers(cx);
+		result
+			.map(drop)
+			.map_err(|e| anyhow!("{e}"))
+			.with_context(|| format!("{} failed in {}", function, self.path.display()))
+	}
+
+	///Runs the script's top level and `on_load`, unless they already ran.
+	fn ensure_loaded(&mut self, cx: &mut Context) -> Result<()> {
+		if self.loaded {
+			return Ok(());
+		}
+		self.loaded = true;
+		self.snapshot.borrow_mut().update(cx.state)?;
+		let result = self.engine.run_ast(&self.ast);
+		self.apply_timers(cx);
+		result
+			.map_err(|e| anyhow!("{e}"))
+			.with_context(|| format!("Could not run {}", self.path.display()))?;
+		self.call(cx, "on_load", ())
+	}
+
+	fn apply_timers(&mut self, cx: &mut Context) {
+		for request in self.timers.borrow_mut().requests.drain(..) {
+			match request {
+				TimerRequest::After(id, delay) => {
+					self.active.insert(id, (cx.timers.after(delay), false));
+				},
+				TimerRequest::Every(id, interval) => {
+					self.active.insert(id, (cx.timers.every(interval), true));
+				},
+				TimerRequest::Cancel(id) => {
+					if let Some((timer, _)) = self.active.remove(&id) {
+						cx.timers.cancel(timer);
+					}
+				},
+			}
+		}
+	}
+} impl std::fmt::Debug for ScriptStep {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ScriptStep")
+			.field("path", &self.path)
+			.field("this", &self.this)
+			.finish_non_exhaustive()
+	}
+} impl MainLoopStep for ScriptStep {
+	fn name(&self) -> &str {
+		&self.name
+	}
+	fn supervision(&self) -> Supervision {
+		Supervision::Restart
+	}
+	fn step(&mut self, cx: &mut Context) -> Result<StepState> {
+		self.ensure_loaded(cx)?;
+		Ok(StepState::KeepActive)
+	}
+	fn on_event(&mut self, cx: &mut Context, event: &Event) -> Result<StepState> {
+		self.ensure_loaded(cx)?;
+		self.call(cx, "on_event", (event_map(event),))?;
+		Ok(StepState::KeepActive)
+	}
+	fn on_timer(&mut self, cx: &mut Context, timer: TimerId) -> Result<StepState> {
+		let Some((&id, &(_, repeats))) = self.active.iter().find(|(_, (active, _))| *active == timer) else {
+			return Ok(StepState::KeepActive);
+		};
+		if !repeats {
+			self.active.remove(&id);
+		}
+		self.call(cx, "on_timer", (id,))?;
+		Ok(StepState::KeepActive)
+	}
+	//Kept, along with `this`, unless the script or its config changed; `load_all` then loads it
+	//again from scratch.
+	fn on_reload(&mut self, _: &mut Context) -> Result<StepState> {
+		let unchanged = config::current().scripting == self.config
+			&& std::fs::read_to_string(&self.path).is_ok_and(|source| source == self.source);
+		Ok(if unchanged { StepState::KeepActive } else { StepState::Done })
+	}
+	fn restart(&mut self, _: &mut Context) -> Result<()> {
+		self.source = Self::read(&self.path)?;
+		self.ast = Self::compile(&self.engine, &self.path, &self.source)?;
+		self.this = Map::new().into();
+		self.loaded = false;
+		self.active.clear();
+		self.timers.borrow_mut().requests.clear();
+		Ok(())
+	}
+}
+
+///Adds a step for every script in the configured directory that isn't running already.
+pub fn load_all(daemon: &mut Daemon) {
+	let config = config::current();
+	if !config.scripting.enabled {
+		return;
+	}
+	let paths = config.scripting.dir().and_then(|dir| {
+		let mut paths = vec![];
+		match std::fs::read_dir(&dir) {
+			Ok(entries) => for entry in entries {
+				let path = entry?.path();
+				if path.extension().is_some_and(|e| e == "rhai") {
+					paths.push(path);
+				}
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+			Err(e) => return Err(e).with_context(|| format!("Could not read {}", dir.display())),
+		}
+		paths.sort();
+		Ok(paths)
+	});
+
+	match paths {
+		Ok(paths) => for path in paths {
+			if daemon.has_step(&ScriptStep::name_for(&path)) {
+				continue;
+			}
+			match ScriptStep::load(&path) {
+				Ok(step) => {
+					info!("Loaded {}", path.display());
+					daemon.add_step(step);
+				},
+				Err(e) => log_error!(&e),
+			}
+		},
+		Err(e) => log_error!(&e),
+	}
+}
@@ -0,0 +1,512 @@
+use anyhow::Result;
+
+use crate::hyprctl::{self, Event, FullscreenMode, WindowAddress};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceState {
+	pub id: i32,
+	pub name: String,
+	pub monitor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorState {
+	pub id: i32,
+	pub name: String,
+	pub description: String,
+	pub width: u32,
+	pub height: u32,
+	pub scale: f32,
+	pub active_workspace: i32,
+	///The special workspace shown on top, if any.
+	pub special_workspace: Option<i32>,
+	pub focused: bool,
+	pub enabled: bool,
+}
+
+///A mapped window, on any workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowState {
+	pub address: WindowAddress,
+	pub class: String,
+	pub title: String,
+	pub workspace_id: i32,
+	pub fullscreen: bool,
+} impl From<hyprctl::Window> for WindowState {
+	fn from(window: hyprctl::Window) -> Self {
+		WindowState {
+			address: window.address,
+			class: window.class,
+			title: window.title,
+			workspace_id: window.workspace.id,
+			fullscreen: window.fullscreen != FullscreenMode::None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveWindowState {
+	pub address: WindowAddress,
+	pub class: String,
+	pub title: String,
+	pub workspace_id: i32,
+	pub workspace_name: String,
+	pub floating: bool,
+	pub fullscreen: bool,
+	pub pinned: bool,
+	///Every window in its group, itself included.
+	pub grouped: Vec<WindowAddress>,
+} impl From<hyprctl::Window> for ActiveWindowState {
+	fn from(window: hyprctl::Window) -> Self {
+		ActiveWindowState {
+			address: window.address,
+			class: window.class,
+			title: window.title,
+			workspace_id: window.workspace.id,
+			workspace_name: window.workspace.name,
+			floating: window.floating,
+			fullscreen: window.fullscreen != FullscreenMode::None,
+			pinned: window.pinned,
+			grouped: window.grouped,
+		}
+	}
+}
+
+///Workspaces, monitors and windows, kept up to date from Hyprland's events so that steps
+///don't have to ask for them on every event. Events that don't say enough (like a workspace being
+///created, but on which monitor?) make the daemon query Hyprland again once the current batch is
+///handled.
+#[derive(Debug, Default)]
+pub struct HyprState {
+	///In the order Hyprland lists them.
+	workspaces: Vec<WorkspaceState>,
+	monitors: Vec<MonitorState>,
+	///In the order Hyprland lists them, then in the order they were opened.
+	windows: Vec<WindowState>,
+	active_window: Option<ActiveWindowState>,
+	///Windows that asked for attention and haven't been focused since, oldest first.
+	urgent: Vec<WindowAddress>,
+	///Bumped on every change.
+	generation: u64,
+	///Workspaces, monitors and windows need to be queried again.
+	stale: bool,
+	active_window_stale: bool,
+} impl HyprState {
+	///Empty until the first [`sync_if_stale`](Self::sync_if_stale).
+	pub fn new() -> Self {
+		HyprState { stale: true, active_window_stale: true, ..Default::default() }
+	}
+
+	pub fn workspaces(&self) -> &[WorkspaceState] {
+		&self.workspaces
+	}
+
+	pub fn monitors(&self) -> &[MonitorState] {
+		&self.monitors
+	}
+
+	pub fn windows(&self) -> &[WindowState] {
+		&self.windows
+	}
+
+	pub fn active_window(&self) -> Option<&ActiveWindowState> {
+		self.active_window.as_ref()
+	}
+
+	pub fn urgent(&self) -> &[WindowAddress] {
+		&self.urgent
+	}
+
+	pub fn is_urgent(&self, address: WindowAddress) -> bool {
+		self.urgent.contains(&address)
+	}
+
+	///Changes whenever anything else here does; steps can compare it with the last one they saw.
+	pub fn generation(&self) -> u64 {
+		self.generation
+	}
+
+	///Replaces workspaces, monitors and windows with what Hyprland reports.
+	fn sync(&mut self) -> Result<()> {
+		self.stale = true;
+		let monitors = hyprctl::monitors()?;
+		let workspaces = hyprctl::workspaces()?;
+		let windows = hyprctl::windows()?;
+
+		self.monitors = monitors.into_iter()
+			.map(|monitor| MonitorState {
+				id: monitor.id,
+				name: monitor.name,
+				description: monitor.description,
+				width: monitor.width,
+				height: monitor.height,
+				scale: monitor.scale,
+				active_workspace: monitor.active_workspace.id,
+				special_workspace: monitor.special_workspace.map(|w| w.id),
+				focused: monitor.focused,
+				enabled: monitor.enabled,
+			})
+			.collect();
+		self.workspaces = workspaces.into_iter()
+			.map(|workspace| WorkspaceState {
+				id: workspace.id,
+				name: workspace.name,
+				monitor: workspace.monitor,
+			})
+			.collect();
+		self.windows = windows.into_iter()
+			.filter(|window| window.mapped)
+			.map(Into::into)
+			.collect();
+		//Windows closed while the cache was stale.
+		self.urgent.retain(|address| self.windows.iter().any(|w| w.address == *address));
+		self.stale = false;
+		self.generation += 1;
+		Ok(())
+	}
+
+	fn sync_active_window(&mut self) -> Result<()> {
+		self.active_window_stale = true;
+		self.active_window = hyprctl::active_window()?.map(Into::into);
+		self.active_window_stale = false;
+		self.generation += 1;
+		Ok(())
+	}
+
+	///Queries whatever events since the last sync could not be applied to.
+	pub fn sync_if_stale(&mut self) -> Result<()> {
+		if self.stale {
+			self.sync()?;
+		}
+		if self.active_window_stale {
+			self.sync_active_window()?;
+		}
+		Ok(())
+	}
+
+	fn id_of(&self, name: &str) -> Option<i32> {
+		self.workspaces.iter().find(|w| w.name == name).map(|w| w.id)
+	}
+
+	///Marks the cache as out of date; always counts as a change.
+	fn invalidate(&mut self) -> bool {
+		self.stale = true;
+		true
+	}
+
+	///Makes `id` the active workspace of the monitor it is on.
+	fn show(&mut self, id: Option<i32>) -> bool {
+		let Some(workspace) = id.and_then(|id| self.workspaces.iter().find(|w| w.id == id)) else {
+			return self.invalidate();
+		};
+		let Some(monitor) = self.monitors.iter_mut().find(|m| m.name == workspace.monitor) else {
+			return self.invalidate();
+		};
+		let changed = monitor.active_workspace != workspace.id;
+		monitor.active_workspace = workspace.id;
+		changed
+	}
+
+	fn focus(&mut self, monitor_name: &str) -> bool {
+		if !self.monitors.iter().any(|m| m.name == monitor_name) {
+			return self.invalidate();
+		}
+		let mut changed = false;
+		for monitor in &mut self.monitors {
+			let focused = monitor.name == monitor_name;
+			changed |= monitor.focused != focused;
+			monitor.focused = focused;
+		}
+		changed
+	}
+
+	fn show_special(&mut self, monitor_name: &str, id: Option<i32>) -> bool {
+		let Some(monitor) = self.monitors.iter_mut().find(|m| m.name == monitor_name) else {
+			return self.invalidate();
+		};
+		let changed = monitor.special_workspace != id;
+		monitor.special_workspace = id;
+		changed
+	}
+
+	///Applies `f` to window `address`.
+	fn update_window(&mut self, address: WindowAddress, f: impl FnOnce(&mut WindowState)) -> bool {
+		match self.windows.iter_mut().find(|w| w.address == address) {
+			Some(window) => {
+				let before = window.clone();
+				f(window);
+				*window != before
+			},
+			None => false,
+		}
+	}
+
+	///Applies `f` to the active window if it is `address`.
+	fn update_active_window(&mut self, address: WindowAddress, f: impl FnOnce(&mut ActiveWindowState)) -> bool {
+		match &mut self.active_window {
+			Some(window) if window.address == address => {
+				let before = window.clone();
+				f(window);
+				*window != before
+			},
+			_ => false,
+		}
+	}
+
+	fn open_window(&mut self, window: WindowState) -> bool {
+		self.windows.retain(|w| w.address != window.address);
+		self.windows.push(window);
+		true
+	}
+
+	fn clear_urgent(&mut self, address: WindowAddress) -> bool {
+		let before = self.urgent.len();
+		self.urgent.retain(|a| *a != address);
+		self.urgent.len() != before
+	}
+
+	fn remove(&mut self, id: Option<i32>) -> bool {
+		let before = self.workspaces.len();
+		self.workspaces.retain(|w| Some(w.id) != id);
+		self.workspaces.len() != before
+	}
+
+	///Hyprland sends most events in an old and a `v2` form; applying both is harmless.
+	pub(super) fn apply(&mut self, event: &Event) {
+		let changed = match event {
+			Event::Workspace { name } => self.show(self.id_of(name)),
+			Event::WorkspaceV2 { id, .. } => self.show(Some(*id)),
+			Event::FocusedMon { workspace_name, monitor_name } => {
+				self.focus(monitor_name) | self.show(self.id_of(workspace_name))
+			},
+			Event::FocusedMonV2 { workspace_id, monitor_name } => {
+				self.focus(monitor_name) | self.show(Some(*workspace_id))
+			},
+			Event::ActiveSpecial { workspace_name, monitor_name } => match workspace_name {
+				Some(name) => match self.id_of(name) {
+					Some(id) => self.show_special(monitor_name, Some(id)),
+					None => self.invalidate(),
+				},
+				None => self.show_special(monitor_name, None),
+			},
+			Event::ActiveSpecialV2 { workspace, monitor_name } => {
+				self.show_special(monitor_name, workspace.as_ref().map(|w| w.id))
+			},
+			Event::DestroyWorkspace { name } => self.remove(self.id_of(name)),
+			Event::DestroyWorkspaceV2 { id, .. } => self.remove(Some(*id)),
+			Event::RenameWorkspace { id, new_name } => match self.workspaces.iter_mut().find(|w| w.id == *id) {
+				Some(workspace) if workspace.name == *new_name => false,
+				Some(workspace) => {
+					workspace.name.clone_from(new_name);
+					true
+				},
+				None => self.invalidate(),
+			},
+			Event::CreateWorkspace { .. } | Event::CreateWorkspaceV2 { .. }
+			| Event::MoveWorkspace { .. } | Event::MoveWorkspaceV2 { .. }
+			| Event::MonitorAdded { .. } | Event::MonitorAddedV2 { .. }
+			| Event::MonitorRemoved { .. } | Event::MonitorRemovedV2 { .. } => self.invalidate(),
+			Event::ConfigReloaded => {
+				self.active_window_stale = true;
+				self.invalidate()
+			},
+			_ => self.apply_to_windows(event),
+		};
+		if changed {
+			self.generation += 1;
+		}
+	}
+
+	///[`apply`](Self::apply) for events about windows. Returns whether anything changed.
+	fn apply_to_windows(&mut self, event: &Event) -> bool {
+		match event {
+			Event::ActiveWindowV2 { window_address: None } => self.active_window.take().is_some(),
+			Event::ActiveWindowV2 { window_address: Some(address) } => {
+				let cleared = self.clear_urgent(*address);
+				if self.active_window.as_ref().is_some_and(|w| w.address == *address) {
+					cleared
+				} else {
+					self.active_window_stale = true;
+					true
+				}
+			},
+			Event::Urgent { window_address } => {
+				let focused = self.active_window.as_ref().is_some_and(|w| w.address == *window_address);
+				if focused || self.urgent.contains(window_address) {
+					false
+				} else {
+					self.urgent.push(*window_address);
+					true
+				}
+			},
+			Event::OpenWindow { window_address, workspace_name, window_class, window_title } => {
+				match self.id_of(workspace_name) {
+					Some(workspace_id) => self.open_window(WindowState {
+						address: *window_address,
+						class: window_class.clone(),
+						title: window_title.clone(),
+						workspace_id,
+						fullscreen: false,
+					}),
+					None => self.invalidate(),
+				}
+			},
+			Event::WindowTitleV2 { window_address, window_title } => {
+				self.update_window(*window_address, |w| w.title.clone_from(window_title))
+				| self.update_active_window(*window_address, |w| w.title.clone_from(window_title))
+			},
+			Event::ChangeFloatingMode { window_address, floating } => {
+				self.update_active_window(*window_address, |w| w.floating = *floating)
+			},
+			Event::Pin { window_address, pinned } => {
+				self.update_active_window(*window_address, |w| w.pinned = *pinned)
+			},
+			Event::MoveWindowV2 { window_address, workspace_id, workspace_name } => {
+				self.update_window(*window_address, |w| w.workspace_id = *workspace_id)
+				| self.update_active_window(*window_address, |w| {
+					w.workspace_id = *workspace_id;
+					w.workspace_name.clone_from(workspace_name);
+				})
+			},
+			//Only sent for the focused window.
+			Event::Fullscreen { active } => match self.active_window.as_ref().map(|w| w.address) {
+				Some(address) => {
+					self.update_window(address, |w| w.fullscreen = *active)
+					| self.update_active_window(address, |w| w.fullscreen = *active)
+				},
+				None => false,
+			},
+			Event::CloseWindow { window_address } => {
+				let listed = self.windows.len();
+				self.windows.retain(|w| w.address != *window_address);
+				(self.windows.len() != listed)
+					| self.clear_urgent(*window_address)
+					| self.active_window.take_if(|w| w.address == *window_address).is_some()
+			},
+			Event::ToggleGroup { .. } | Event::MoveIntoGroup { .. } | Event::MoveOutOfGroup { .. } => {
+				self.active_window_stale = true;
+				true
+			},
+			_ => false,
+		}
+	}
+}
+
+#[cfg(test)]
+impl HyprState {
+	///A synced state with these contents, without asking Hyprland.
+	pub(super) fn seeded(workspaces: Vec<WorkspaceState>, monitors: Vec<MonitorState>, windows: Vec<WindowState>) -> Self {
+		HyprState { workspaces, monitors, windows, generation: 1, ..Default::default() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn window(address: WindowAddress, workspace_id: i32) -> WindowState {
+		WindowState { address, class: String::from("kitty"), title: String::from("~"), workspace_id, fullscreen: false }
+	}
+
+	///Workspaces 1 and 2 on DP-1, showing 1, with window 0xa on it.
+	fn seeded() -> HyprState {
+		let workspace = |id, name: &str| WorkspaceState { id, name: name.to_string(), monitor: String::from("DP-1") };
+		HyprState::seeded(
+			vec![workspace(1, "1"), workspace(2, "2")],
+			vec![MonitorState {
+				id: 0,
+				name: String::from("DP-1"),
+				description: String::new(),
+				width: 1920,
+				height: 1080,
+				scale: 1.0,
+				active_workspace: 1,
+				special_workspace: None,
+				focused: true,
+				enabled: true,
+			}],
+			vec![window(0xa, 1)],
+		)
+	}
+
+	#[test]
+	fn generation_only_changes_with_the_state() {
+		let mut state = seeded();
+		let generation = state.generation();
+		state.apply(&Event::Workspace { name: String::from("2") });
+		assert_eq!(state.monitors()[0].active_workspace, 2);
+		assert_eq!(state.generation(), generation + 1);
+
+		state.apply(&Event::WorkspaceV2 { id: 2, name: String::from("2") });
+		state.apply(&Event::ActiveLayout { keyboard_name: String::from("kbd"), layout_name: String::from("German") });
+		assert_eq!(state.generation(), generation + 1);
+		assert!(!state.stale);
+	}
+
+	#[test]
+	fn unknown_workspaces_invalidate() {
+		let mut state = seeded();
+		let generation = state.generation();
+		state.apply(&Event::WorkspaceV2 { id: 3, name: String::from("3") });
+		assert!(state.stale);
+		assert_eq!(state.generation(), generation + 1);
+
+		let mut state = seeded();
+		state.apply(&Event::RenameWorkspace { id: 3, new_name: String::from("web") });
+		assert!(state.stale);
+	}
+
+	#[test]
+	fn windows_open_move_and_close() {
+		let mut state = seeded();
+		state.apply(&Event::OpenWindow {
+			window_address: 0xb,
+			workspace_name: String::from("2"),
+			window_class: String::from("firefox"),
+			window_title: String::from("Start"),
+		});
+		assert_eq!(state.windows().iter().map(|w| (w.address, w.workspace_id)).collect::<Vec<_>>(), [(0xa, 1), (0xb, 2)]);
+
+		let generation = state.generation();
+		state.apply(&Event::MoveWindowV2 { window_address: 0xa, workspace_id: 2, workspace_name: String::from("2") });
+		assert_eq!(state.windows()[0].workspace_id, 2);
+		assert_eq!(state.generation(), generation + 1);
+
+		state.apply(&Event::CloseWindow { window_address: 0xa });
+		assert_eq!(state.windows().iter().map(|w| w.address).collect::<Vec<_>>(), [0xb]);
+		assert_eq!(state.generation(), generation + 2);
+		assert!(!state.stale);
+	}
+
+	#[test]
+	fn windows_on_unknown_workspaces_invalidate() {
+		let mut state = seeded();
+		state.apply(&Event::OpenWindow {
+			window_address: 0xb,
+			workspace_name: String::from("3"),
+			window_class: String::from("kitty"),
+			window_title: String::from("~"),
+		});
+		assert!(state.stale);
+	}
+
+	#[test]
+	fn urgent_is_cleared_on_focus_and_close() {
+		let mut state = seeded();
+		state.apply(&Event::OpenWindow {
+			window_address: 0xb,
+			workspace_name: String::from("2"),
+			window_class: String::from("firefox"),
+			window_title: String::from("Start"),
+		});
+		state.apply(&Event::Urgent { window_address: 0xa });
+		state.apply(&Event::Urgent { window_address: 0xb });
+		state.apply(&Event::Urgent { window_address: 0xa });
+		assert_eq!(state.urgent(), [0xa, 0xb]);
+
+		state.apply(&Event::ActiveWindowV2 { window_address: Some(0xa) });
+		assert_eq!(state.urgent(), [0xb]);
+		state.apply(&Event::CloseWindow { window_address: 0xb });
+		assert!(state.urgent().is_empty());
+	}
+}
@@ -0,0 +1,180 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::Write,
+	time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hyprctl;
+
+use super::{client::Client, handshake::BINARY_VERSION, Daemon, StepStatus};
+
+///Window for [`EventKindStatus::last_minute`].
+const RECENT: Duration = Duration::from_mins(1);
+
+fn millis(duration: Duration) -> u64 {
+	u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn micros(duration: Duration) -> u64 {
+	u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+///Counts Hyprland events by kind.
+#[derive(Debug, Default)]
+pub struct EventStats {
+	kinds: HashMap<String, KindStats>,
+}
+
+#[derive(Debug, Default)]
+struct KindStats {
+	count: u64,
+	///When each event of the last [`RECENT`] arrived.
+	recent: VecDeque<Instant>,
+}
+impl KindStats {
+	fn prune(&mut self, now: Instant) {
+		while self.recent.front().is_some_and(|time| now.duration_since(*time) > RECENT) {
+			self.recent.pop_front();
+		}
+	}
+}
+
+impl EventStats {
+	pub fn record(&mut self, kind: &str) {
+		let now = Instant::now();
+		let stats = match self.kinds.get_mut(kind) {
+			Some(stats) => stats,
+			None => self.kinds.entry(kind.to_string()).or_default(),
+		};
+		stats.count += 1;
+		stats.recent.push_back(now);
+		stats.prune(now);
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+	///homehelper's own version.
+	pub version: String,
+	pub pid: u32,
+	pub uptime_ms: u64,
+	pub hyprland: HyprlandStatus,
+	pub clients: Vec<ClientStatus>,
+	pub steps: Vec<StepStatus>,
+	///Requests made to Hyprland, by name.
+	pub socket1: Vec<CallStatus>,
+	pub events: Vec<EventKindStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HyprlandStatus {
+	pub instance: Option<String>,
+	pub version: Option<String>,
+	///Why `version` could not be fetched.
+	pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientStatus {
+	pub id: u64,
+	///Unset until the client has sent one.
+	pub command: Option<String>,
+	pub connected_ms: u64,
+	pub queued_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallStatus {
+	pub request: String,
+	pub count: u64,
+	pub errors: u64,
+	pub mean_us: u64,
+	pub max_us: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventKindStatus {
+	pub kind: String,
+	pub count: u64,
+	pub last_minute: usize,
+	///Since the daemon started.
+	pub per_second: f64,
+}
+
+fn client_status(client: &Client) -> ClientStatus {
+	ClientStatus {
+		id: client.id(),
+		command: client.command().map(String::from),
+		connected_ms: millis(client.connected_for()),
+		queued_bytes: client.queued_bytes(),
+	}
+}
+
+impl Daemon {
+	///`requester` is the client asking, whose connection isn't in `connections` while it is
+	///handled.
+	pub fn status(&mut self, requester: &Client) -> DaemonStatus {
+		let uptime = self.started.elapsed();
+		let now = Instant::now();
+
+		let (version, error) = match hyprctl::version() {
+			Ok(v) => {
+				let mut version = if v.version.is_empty() { v.tag } else { v.version };
+				if !v.commit.is_empty() {
+					let _ = write!(version, " ({})", &v.commit[..v.commit.len().min(8)]);
+				}
+				(Some(version), None)
+			},
+			Err(e) => (None, Some(format!("{e:#}"))),
+		};
+
+		let mut clients: Vec<ClientStatus> = self.connections.iter()
+			.map(|c| client_status(c.client()))
+			.chain([client_status(requester)])
+			.collect();
+		clients.sort_by_key(|c| c.id);
+
+		let mut socket1: Vec<CallStatus> = hyprctl::call_stats().into_iter()
+			.map(|(request, stats)| CallStatus {
+				request,
+				count: stats.count,
+				errors: stats.errors,
+				mean_us: micros(stats.total) / stats.count.max(1),
+				max_us: micros(stats.max),
+			})
+			.collect();
+		socket1.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.request.cmp(&b.request)));
+
+		let mut events: Vec<EventKindStatus> = self.events.kinds.iter_mut()
+			.map(|(kind, stats)| {
+				stats.prune(now);
+				#[allow(clippy::cast_precision_loss)] //Only an estimate.
+				let per_second = stats.count as f64 / uptime.as_secs_f64().max(1.0);
+				EventKindStatus {
+					kind: kind.clone(),
+					count: stats.count,
+					last_minute: stats.recent.len(),
+					per_second,
+				}
+			})
+			.collect();
+		events.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.kind.cmp(&b.kind)));
+
+		DaemonStatus {
+			version: BINARY_VERSION.to_string(),
+			pid: std::process::id(),
+			uptime_ms: millis(uptime),
+			hyprland: HyprlandStatus {
+				instance: std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok(),
+				version,
+				error,
+			},
+			clients,
+			steps: self.step_statuses(),
+			socket1,
+			events,
+		}
+	}
+}
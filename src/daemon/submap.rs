@@ -2,23 +2,25 @@ use std::process::{Child, Command, Stdio};
 
 use anyhow::Result;
 
-use crate::hyprctl::{self, Bind};
+use crate::{config, hyprctl::Bind};
 
 pub fn open_kitty(cmd: &str, lines: usize, longest_line: usize) -> Result<Child> {
+    let config = &config::current().submap;
     let mut c = Command::new("kitty");
 
 	//This is why we can't have rustfmt
     let args = [
         "+kitten", "panel",
-		"--edge", "center-sized",
-        "--layer", "top",
+		"--edge", &config.edge,
+        "--layer", &config.layer,
         "--lines", &lines.to_string(),
         "--columns", &longest_line.to_string(),
-        "--app-id", "homehelper-submap",
-        "sh", "-c", cmd,
+        "--app-id", &config.app_id,
     ];
 
     c.args(args);
+    c.args(&config.extra_args);
+    c.args(["sh", "-c", cmd]);
     c.stdout(Stdio::null());
     c.stderr(Stdio::null());
     Ok(c.spawn()?)
@@ -53,10 +55,9 @@ pub fn show_binds(binds: &[Bind]) -> Result<Child> {
     Ok(cp)
 }
 
-pub fn show_binds_in_submap(name: &str) -> Result<Child> {
-    let all_binds = hyprctl::binds()?;
+pub fn show_binds_in_submap(all_binds: &[Bind], name: &str) -> Result<Child> {
     let next_binds: Vec<Bind> = all_binds
-        .into_iter()
+        .iter()
         .filter(|b| {
             if let Some(submap) = &b.submap {
                 *submap == name && b.description.is_some()
@@ -64,6 +65,7 @@ pub fn show_binds_in_submap(name: &str) -> Result<Child> {
                 false
             }
         })
+        .cloned()
         .collect();
 
     show_binds(&next_binds)
@@ -0,0 +1,245 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, log_error, info, warn};
+
+use super::{state::HyprState, timers::Timers, Context, MainLoopStep, StepState};
+
+///What the daemon does with a step whose `on_error` gives up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Supervision {
+	///Pause the step, waiting longer after each consecutive failure, then call
+	///[`MainLoopStep::restart`] and carry on.
+	Restart,
+	///Remove the step and keep the daemon running.
+	Drop,
+	///Remove the step and stop the daemon.
+	Escalate,
+}
+
+///A step plus everything the daemon tracks about it.
+#[derive(Debug)]
+pub struct StepSlot {
+	///Unique for this step, e.g. `listen-eww#3`.
+	pub name: String,
+	step: Box<dyn MainLoopStep>,
+	supervision: Supervision,
+	failures: u32,
+	restarts: u32,
+	///Events handed to the step.
+	events: u64,
+	///Failures since the step last ran long enough to be considered healthy.
+	consecutive_failures: u32,
+	last_failure: Option<Instant>,
+	last_error: Option<String>,
+	suspended_until: Option<Instant>,
+	timers: Timers,
+}
+
+///What a supervised call decided about the step.
+pub enum Outcome {
+	Keep,
+	Remove,
+	///Remove the step and stop the daemon with this error.
+	Escalate(anyhow::Error),
+}
+
+impl StepSlot {
+	pub fn new(id: u64, step: Box<dyn MainLoopStep>) -> Self {
+		StepSlot {
+			name: format!("{}#{id}", step.name()),
+			supervision: step.supervision(),
+			step,
+			failures: 0,
+			restarts: 0,
+			events: 0,
+			consecutive_failures: 0,
+			last_failure: None,
+			last_error: None,
+			suspended_until: None,
+			timers: Timers::default(),
+		}
+	}
+
+	///Runs `f` on the step unless it is waiting to be restarted, applying its supervision policy if
+	///it fails. `event` is only used for logging.
+	pub fn run(&mut self, state: &HyprState, event: Option<&str>, f: impl FnOnce(&mut dyn MainLoopStep, &mut Context) -> Result<StepState>) -> Outcome {
+		if let Some(until) = self.suspended_until {
+			if Instant::now() < until {
+				return Outcome::Keep;
+			}
+			self.suspended_until = None;
+			self.restarts += 1;
+			info!(step = &self.name; "Restarting after {} consecutive failures", self.consecutive_failures);
+			let mut cx = Context { timers: &mut self.timers, state };
+			if let Err(e) = self.step.restart(&mut cx) {
+				return self.fail(None, e);
+			}
+		}
+
+		self.events += u64::from(event.is_some());
+		let mut cx = Context { timers: &mut self.timers, state };
+		let result = match f(self.step.as_mut(), &mut cx) {
+			Ok(result) => Ok(result),
+			Err(e) => self.step.on_error(e),
+		};
+		match result {
+			Ok(StepState::KeepActive) => Outcome::Keep,
+			Ok(StepState::Done) => Outcome::Remove,
+			Err(e) => self.fail(event, e),
+		}
+	}
+
+	///Hands a config reload to the step, even while it waits to be restarted: steps end
+	///themselves in `on_reload` when they are disabled or replaced, and a suspended one must not be
+	///left behind. It stays suspended if it carries on.
+	pub fn reload(&mut self, state: &HyprState) -> Outcome {
+		let suspended_until = self.suspended_until.take();
+		let outcome = self.run(state, None, |step, cx| step.on_reload(cx));
+		if matches!(outcome, Outcome::Keep) && self.suspended_until.is_none() {
+			self.suspended_until = suspended_until;
+		}
+		outcome
+	}
+
+	///Fires the step's timers that are due at `now`.
+	pub fn run_timers(&mut self, state: &HyprState, now: Instant) -> Outcome {
+		if self.suspended_until.is_some() {
+			return Outcome::Keep;
+		}
+		for id in self.timers.take_due(now) {
+			match self.run(state, None, |step, cx| step.on_timer(cx, id)) {
+				Outcome::Keep => {},
+				outcome => return outcome,
+			}
+		}
+		Outcome::Keep
+	}
+
+	///When the main loop must wake up for this step at the latest.
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.suspended_until.or_else(|| self.timers.next_deadline())
+	}
+
+	fn fail(&mut self, event: Option<&str>, error: anyhow::Error) -> Outcome {
+		let config = config::current();
+		let now = Instant::now();
+		//A step that stayed up for a while is healthy again, so start the backoff over.
+		if self.last_failure.is_some_and(|last| now.duration_since(last) > config.daemon.max_restart_backoff() * 2) {
+			self.consecutive_failures = 0;
+		}
+		self.failures += 1;
+		self.consecutive_failures += 1;
+		self.last_failure = Some(now);
+		self.last_error = Some(format!("{error:#}"));
+
+		match event {
+			Some(event) => log_error!(step = &self.name, event = event; &error),
+			None => log_error!(step = &self.name; &error),
+		}
+
+		match self.supervision {
+			Supervision::Restart => {
+				let backoff = config.daemon.restart_backoff()
+					.saturating_mul(2u32.saturating_pow(self.consecutive_failures - 1))
+					.min(config.daemon.max_restart_backoff());
+				warn!(step = &self.name; "Restarting in {backoff:?}");
+				self.suspended_until = Some(now + backoff);
+				self.timers.cancel_all();
+				Outcome::Keep
+			},
+			Supervision::Drop => {
+				warn!(step = &self.name; "Removed after failing");
+				Outcome::Remove
+			},
+			Supervision::Escalate => Outcome::Escalate(error.context(format!("Step {} failed", self.name))),
+		}
+	}
+
+	///[`MainLoopStep::name`], without the id.
+	#[cfg(feature = "scripting")]
+	pub fn step_name(&self) -> &str {
+		self.step.name()
+	}
+
+	pub fn status(&self) -> StepStatus {
+		StepStatus {
+			name: self.name.clone(),
+			supervision: self.supervision,
+			failures: self.failures,
+			restarts: self.restarts,
+			events: self.events,
+			last_error: self.last_error.clone(),
+			restarting_in_ms: self.suspended_until.map(|until| {
+				u64::try_from(until.saturating_duration_since(Instant::now()).as_millis()).unwrap_or(u64::MAX)
+			}),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepStatus {
+	pub name: String,
+	pub supervision: Supervision,
+	pub failures: u32,
+	pub restarts: u32,
+	pub events: u64,
+	pub last_error: Option<String>,
+	///Set while the step is waiting to be restarted.
+	pub restarting_in_ms: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+	use anyhow::bail;
+
+	use super::*;
+	use crate::config::Config;
+
+	///Fails on every `step`, and ends on reload if `done_on_reload`.
+	#[derive(Debug)]
+	struct Failing {
+		done_on_reload: bool,
+	} impl MainLoopStep for Failing {
+		fn name(&self) -> &'static str {
+			"failing"
+		}
+		fn supervision(&self) -> Supervision {
+			Supervision::Restart
+		}
+		fn step(&mut self, _: &mut Context) -> Result<StepState> {
+			bail!("failed")
+		}
+		fn on_reload(&mut self, _: &mut Context) -> Result<StepState> {
+			Ok(if self.done_on_reload { StepState::Done } else { StepState::KeepActive })
+		}
+	}
+
+	fn suspended(done_on_reload: bool) -> StepSlot {
+		let mut config = Config::default();
+		config.notifications.enabled = false;
+		config::set(config);
+
+		let mut slot = StepSlot::new(1, Box::new(Failing { done_on_reload }));
+		assert!(matches!(slot.run(&HyprState::new(), None, |step, cx| step.step(cx)), Outcome::Keep));
+		assert!(slot.status().restarting_in_ms.is_some());
+		slot
+	}
+
+	#[test]
+	fn suspended_step_can_end_on_reload() {
+		let mut slot = suspended(true);
+		assert!(matches!(slot.reload(&HyprState::new()), Outcome::Remove));
+	}
+
+	#[test]
+	fn suspended_step_stays_suspended_after_reload() {
+		let mut slot = suspended(false);
+		assert!(matches!(slot.reload(&HyprState::new()), Outcome::Keep));
+		assert!(slot.status().restarting_in_ms.is_some());
+		assert_eq!(slot.status().restarts, 0);
+	}
+}
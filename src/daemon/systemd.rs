@@ -0,0 +1,164 @@
+use std::{
+	os::{
+		fd::FromRawFd,
+		linux::net::SocketAddrExt,
+		unix::net::{SocketAddr, UnixDatagram, UnixListener},
+	},
+	time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+///First file descriptor passed by socket activation, see `sd_listen_fds(3)`.
+const SD_LISTEN_FDS_START: i32 = 3;
+
+///Takes over the listener systemd passed in, if this process was socket-activated.
+pub fn listen_fds() -> Result<Option<UnixListener>> {
+	let for_us = std::env::var("LISTEN_PID").ok()
+		.and_then(|pid| pid.parse::<u32>().ok())
+		.is_some_and(|pid| pid == std::process::id());
+	let count: i32 = std::env::var("LISTEN_FDS").ok()
+		.and_then(|n| n.parse().ok())
+		.unwrap_or(0);
+	//SAFETY: called from `Daemon::new`, before any other thread exists. Children must not inherit
+	//these.
+	unsafe {
+		std::env::remove_var("LISTEN_PID");
+		std::env::remove_var("LISTEN_FDS");
+		std::env::remove_var("LISTEN_FDNAMES");
+	}
+
+	if !for_us || count == 0 {
+		return Ok(None);
+	}
+	if count > 1 {
+		bail!("Expected one socket from systemd, got {count}");
+	}
+	//SAFETY: systemd hands this descriptor to us and nobody else owns it.
+	let listener = unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+	listener.set_nonblocking(true)?;
+	Ok(Some(listener))
+}
+
+///Sends a state update to systemd, see `sd_notify(3)`. Does nothing outside of a `Type=notify`
+///service.
+pub fn notify(state: &str) {
+	let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+		return;
+	};
+	let addr = match path.strip_prefix('@') {
+		Some(name) => SocketAddr::from_abstract_name(name),
+		None => SocketAddr::from_pathname(&path),
+	};
+	let sent = addr.and_then(|addr| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr));
+	if let Err(e) = sent {
+		crate::warn!("Could not notify systemd of {state:?}: {e}");
+	}
+}
+
+///Pings systemd often enough to satisfy `WatchdogSec=`.
+#[derive(Debug)]
+pub struct Watchdog {
+	interval: Duration,
+	last: Instant,
+}
+impl Watchdog {
+	pub fn from_env() -> Option<Self> {
+		if let Ok(pid) = std::env::var("WATCHDOG_PID")
+			&& pid.parse::<u32>().ok() != Some(std::process::id())
+		{
+			return None;
+		}
+		let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+		Some(Watchdog {
+			//Half the timeout, as recommended by `sd_watchdog_enabled(3)`.
+			interval: Duration::from_micros(usec / 2),
+			last: Instant::now(),
+		})
+	}
+
+	pub fn next_ping(&self) -> Instant {
+		self.last + self.interval
+	}
+
+	pub fn ping_if_due(&mut self) {
+		if Instant::now() >= self.next_ping() {
+			notify("WATCHDOG=1");
+			self.last = Instant::now();
+		}
+	}
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct Arguments {
+	///Also install a socket unit, so the daemon starts on the first `homehelper remote`.
+	#[arg(long)]
+	socket: bool,
+	///Overwrite existing unit files.
+	#[arg(short, long)]
+	force: bool,
+}
+
+const SOCKET_UNIT: &str = "\
+[Unit]
+Description=homehelper daemon socket
+PartOf=graphical-session.target
+
+[Socket]
+ListenStream=%t/homehelper.sock
+
+[Install]
+WantedBy=graphical-session.target
+";
+
+fn service_unit(exe: &str, socket: bool) -> String {
+	let environment = if socket {
+		"Environment=HOMEHELPER_SOCKET=%t/homehelper.sock\n"
+	} else {
+		""
+	};
+	format!("\
+[Unit]
+Description=homehelper daemon for Hyprland
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=notify
+ExecStart={exe} daemon
+ExecReload=kill -HUP $MAINPID
+{environment}Restart=on-failure
+WatchdogSec=30
+
+[Install]
+WantedBy=graphical-session.target
+")
+}
+
+pub fn install_service(arguments: &Arguments) -> Result<()> {
+	let exe = std::env::current_exe().context("Could not find the homehelper binary")?;
+	let dir = crate::config::config_home()?.join("systemd").join("user");
+	std::fs::create_dir_all(&dir)?;
+
+	let mut units = vec![("homehelper.service", service_unit(&exe.to_string_lossy(), arguments.socket))];
+	if arguments.socket {
+		units.push(("homehelper.socket", SOCKET_UNIT.to_string()));
+	}
+	for (name, contents) in &units {
+		let path = dir.join(name);
+		if path.exists() && !arguments.force {
+			bail!("{} already exists. Pass --force to overwrite it.", path.display());
+		}
+		std::fs::write(&path, contents).with_context(|| format!("Could not write {}", path.display()))?;
+		println!("Wrote {}", path.display());
+	}
+
+	let unit = if arguments.socket { "homehelper.socket" } else { "homehelper.service" };
+	println!("\nHyprland's environment must be visible to systemd. Add this to hyprland.conf:");
+	println!("    exec-once = systemctl --user import-environment HYPRLAND_INSTANCE_SIGNATURE WAYLAND_DISPLAY && systemctl --user start {unit}");
+	if arguments.socket {
+		println!("and set HOMEHELPER_SOCKET=$XDG_RUNTIME_DIR/homehelper.sock wherever `homehelper remote` runs (e.g. `env = ` in hyprland.conf).");
+	}
+	println!("Then run: systemctl --user daemon-reload && systemctl --user enable {unit}");
+	Ok(())
+}
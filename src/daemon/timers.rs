@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+///Identifies one timer of one step. Passed back to [`MainLoopStep::on_timer`](super::MainLoopStep::on_timer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+#[derive(Debug)]
+struct Timer {
+	id: TimerId,
+	deadline: Instant,
+	///Set for timers created with [`Timers::every`].
+	interval: Option<Duration>,
+}
+
+///A step's pending wake-ups. The main loop sleeps until the earliest one across all steps.
+#[derive(Debug, Default)]
+pub struct Timers {
+	timers: Vec<Timer>,
+	next_id: u64,
+}
+impl Timers {
+	fn add(&mut self, deadline: Instant, interval: Option<Duration>) -> TimerId {
+		self.next_id += 1;
+		let id = TimerId(self.next_id);
+		self.timers.push(Timer { id, deadline, interval });
+		id
+	}
+
+	///Fires once at `deadline`, or as soon as possible if it has passed.
+	pub fn at(&mut self, deadline: Instant) -> TimerId {
+		self.add(deadline, None)
+	}
+
+	///Fires once after `delay`.
+	pub fn after(&mut self, delay: Duration) -> TimerId {
+		self.add(Instant::now() + delay, None)
+	}
+
+	///Fires every `interval`, starting one `interval` from now, until cancelled. Missed ticks are
+	///skipped rather than fired in a burst.
+	pub fn every(&mut self, interval: Duration) -> TimerId {
+		self.add(Instant::now() + interval, Some(interval.max(Duration::from_millis(1))))
+	}
+
+	///Returns whether the timer was still pending.
+	pub fn cancel(&mut self, id: TimerId) -> bool {
+		let before = self.timers.len();
+		self.timers.retain(|timer| timer.id != id);
+		self.timers.len() != before
+	}
+
+	pub fn cancel_all(&mut self) {
+		self.timers.clear();
+	}
+
+	pub fn is_pending(&self, id: TimerId) -> bool {
+		self.timers.iter().any(|timer| timer.id == id)
+	}
+
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.timers.iter().map(|timer| timer.deadline).min()
+	}
+
+	///Removes the timers due at `now`, in deadline order, rescheduling the repeating ones.
+	pub(super) fn take_due(&mut self, now: Instant) -> Vec<TimerId> {
+		let mut due: Vec<(Instant, TimerId)> = vec![];
+		self.timers.retain_mut(|timer| {
+			if timer.deadline > now {
+				return true;
+			}
+			due.push((timer.deadline, timer.id));
+			match timer.interval {
+				Some(interval) => {
+					while timer.deadline <= now {
+						timer.deadline += interval;
+					}
+					true
+				},
+				None => false,
+			}
+		});
+		due.sort_by_key(|(deadline, _)| *deadline);
+		due.into_iter().map(|(_, id)| id).collect()
+	}
+}
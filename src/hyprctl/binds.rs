@@ -80,6 +80,17 @@ impl From<BindInternal> for Bind {
         }
     }
 }
+impl Bind {
+    ///Names of the modifiers in `modmask`, as Hyprland's config spells them.
+    #[must_use]
+    pub fn modifiers(&self) -> Vec<&'static str> {
+        const NAMES: [&str; 8] = ["SHIFT", "CAPS", "CTRL", "ALT", "MOD2", "MOD3", "SUPER", "MOD5"];
+        NAMES.iter().enumerate()
+            .filter(|(bit, _)| self.modmask & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
 
 pub fn binds() -> Result<Vec<Bind>> {
     Ok(
@@ -89,3 +100,18 @@ pub fn binds() -> Result<Vec<Bind>> {
             .collect(),
     )
 }
+
+///The submap Hyprland is in, `None` outside of submaps.
+///
+///# Errors
+///If Hyprland can't be reached, or [`Error::Rejected`](super::Error::Rejected) before Hyprland 0.40,
+///which doesn't know the request.
+pub fn submap() -> Result<Option<String>> {
+    let reply = send_command(b"/submap")?;
+    let name = reply.trim();
+    //Hyprland before 0.40 doesn't know this request.
+    if name == "unknown request" {
+        return Err(super::Error::Rejected(reply).into());
+    }
+    Ok(if name == "default" { None } else { Some(name.to_string()) })
+}
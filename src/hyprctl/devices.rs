@@ -0,0 +1,24 @@
+use super::send_command;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyboard {
+    pub name: String,
+    ///Name of the current layout, like `English (US)`.
+    pub active_keymap: String,
+    ///The keyboard that `switchxkblayout current` and the like act on.
+    pub main: bool,
+}
+
+///Only the devices homehelper cares about so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Devices {
+    pub keyboards: Vec<Keyboard>,
+}
+
+///# Errors
+///If Hyprland can't be reached or its answer can't be parsed.
+pub fn devices() -> Result<Devices> {
+    Ok(serde_json::from_str(&send_command(b"j/devices")?)?)
+}
@@ -0,0 +1,18 @@
+use super::{expect_ok, send_command};
+use anyhow::Result;
+
+///Runs a dispatcher, like `hyprctl dispatch workspace 2`.
+///
+///# Errors
+///If Hyprland can't be reached or doesn't answer `ok`, like for an unknown dispatcher.
+pub fn dispatch(dispatcher: &str, args: &str) -> Result<()> {
+    expect_ok(&send_command(format!("/dispatch {dispatcher} {args}").as_bytes())?)
+}
+
+///Sets a config option until the config is next reloaded, like `hyprctl keyword general:gaps_in 4`.
+///
+///# Errors
+///If Hyprland can't be reached or doesn't answer `ok`, like for an unknown option or a bad value.
+pub fn keyword(keyword: &str, value: &str) -> Result<()> {
+    expect_ok(&send_command(format!("/keyword {keyword} {value}").as_bytes())?)
+}
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::os::unix;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use unix::net::UnixStream;
 
-use anyhow::{bail, Result};
+use anyhow::{Context, Result};
 
 pub mod notify;
 pub use notify::*;
@@ -15,6 +17,14 @@ pub mod workspaces;
 pub use workspaces::*;
 pub mod monitors;
 pub use monitors::*;
+pub mod dispatch;
+pub use dispatch::*;
+pub mod version;
+pub use version::*;
+pub mod windows;
+pub use windows::*;
+pub mod devices;
+pub use devices::*;
 
 pub mod prelude {
 	pub use crate::hyprctl;
@@ -37,21 +47,61 @@ pub static SOCKET2: LazyLock<String> = LazyLock::new(|| {
     format!("{runtime}/hypr/{his}/.socket2.sock")
 });
 
+///Failures on Hyprland's side, as opposed to problems with homehelper itself.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Hyprland request `{0}` failed")]
+    Request(String),
+    #[error("Command returned: {0}")]
+    Rejected(String),
+}
+
+///How one kind of socket1 request has gone so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallStats {
+    pub count: u64,
+    ///Requests that never got an answer. Answers that are errors count as answers.
+    pub errors: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+static CALL_STATS: LazyLock<Mutex<HashMap<String, CallStats>>> = LazyLock::new(Mutex::default);
+
+///Stats for every request made so far, by request name (e.g. `j/monitors`, `/dispatch`).
+pub fn call_stats() -> HashMap<String, CallStats> {
+    CALL_STATS.lock().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
 fn send_command(command: &[u8]) -> Result<String> {
-    let mut sock = UnixStream::connect(&*SOCKET1)?;
-    sock.write_all(command)?;
-    let mut result = vec![];
-    sock.read_to_end(&mut result)?;
-    let result_str = String::from_utf8(result)?;
+    let send = || -> Result<String> {
+        let mut sock = UnixStream::connect(&*SOCKET1)?;
+        sock.write_all(command)?;
+        let mut result = vec![];
+        sock.read_to_end(&mut result)?;
+        Ok(String::from_utf8(result)?)
+    };
+
+    let start = Instant::now();
+    let result = send();
+    let elapsed = start.elapsed();
+
+    let name = command.split(|b| *b == b' ').next().unwrap_or_default();
+    let mut stats = CALL_STATS.lock().unwrap_or_else(PoisonError::into_inner);
+    let stats = stats.entry(String::from_utf8_lossy(name).into_owned()).or_default();
+    stats.count += 1;
+    stats.errors += u64::from(result.is_err());
+    stats.total += elapsed;
+    stats.max = stats.max.max(elapsed);
 
-    Ok(result_str)
+    result.with_context(|| Error::Request(String::from_utf8_lossy(command).into_owned()))
 }
 
 fn expect_ok(result: &str) -> Result<()> {
     if result == "ok" {
         Ok(())
     } else {
-        bail!("Command returned: {result}");
+        Err(Error::Rejected(result.to_string()).into())
     }
 }
 
@@ -2,15 +2,18 @@
 
 use super::{expect_ok, send_command};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::str::FromStr;
 use std::time::Duration;
 
 #[allow(unused)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
 #[repr(i8)]
 pub enum NotifyIcon {
     None = -1,
     Warning = 0,
+    #[default]
     Info = 1,
     Hint = 2,
     Error = 3,
@@ -18,6 +21,7 @@ pub enum NotifyIcon {
     Ok = 5,
 }
 
+///Written and read as text everywhere, like `#ff0000`, so configs and JSON remotes can use it.
 #[allow(unused)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
@@ -28,18 +32,161 @@ impl Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Color::Rgb(r, g, b) => write!(f, "rgb({r},{g},{b})"),
-            Color::Rgba(r, g, b, a) => write!(f, "rgba({r},{g},{b},{a})"),
+            //Hyprland reads a decimal alpha as 0-1, so hex leaves no doubt.
+            Color::Rgba(r, g, b, a) => write!(f, "rgba({r:02x}{g:02x}{b:02x}{a:02x})"),
         }
     }
 }
 
-pub fn notify(icon: NotifyIcon, time: Duration, color: Color, message: &str) -> Result<()> {
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid color `{0}`, expected #rrggbb, #rrggbbaa, rgb(r,g,b), rgba(r,g,b,a) with a from 0 to 255, rgb(rrggbb) or rgba(rrggbbaa)")]
+pub struct ParseColorError(String);
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseColorError(s.to_string());
+        let hex = |h: &str| -> Result<Vec<u8>, ParseColorError> {
+            if !h.is_ascii() || !h.len().is_multiple_of(2) {
+                return Err(error());
+            }
+            (0..h.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&h[i..i + 2], 16).map_err(|_| error()))
+                .collect()
+        };
+        //Unlike Hyprland's, the alpha in `rgba(r,g,b,a)` goes from 0 to 255 like the other parts.
+        let components = |c: &str| -> Result<Vec<u8>, ParseColorError> {
+            if !c.contains(',') {
+                return hex(c.trim());
+            }
+            c.split(',')
+                .map(|n| n.trim().parse().map_err(|_| error()))
+                .collect()
+        };
+
+        let s = s.trim();
+        let parts = if let Some(h) = s.strip_prefix('#') {
+            hex(h)?
+        } else if let Some(c) = s.strip_prefix("rgba(").and_then(|c| c.strip_suffix(')')) {
+            let parts = components(c)?;
+            if parts.len() != 4 {
+                return Err(error());
+            }
+            parts
+        } else if let Some(c) = s.strip_prefix("rgb(").and_then(|c| c.strip_suffix(')')) {
+            let parts = components(c)?;
+            if parts.len() != 3 {
+                return Err(error());
+            }
+            parts
+        } else {
+            return Err(error());
+        };
+
+        match parts[..] {
+            [r, g, b] => Ok(Color::Rgb(r, g, b)),
+            [r, g, b, a] => Ok(Color::Rgba(r, g, b, a)),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+///Everything `hyprctl notify` can set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub icon: NotifyIcon,
+    pub time: Duration,
+    ///Hyprland picks one to match the icon if unset.
+    pub color: Option<Color>,
+    pub font_size: Option<u32>,
+    pub message: String,
+}
+
+///Shows a notification, like `hyprctl notify`.
+///
+///# Errors
+///If Hyprland can't be reached or doesn't answer `ok`.
+pub fn send_notification(notification: &Notification) -> Result<()> {
+    let color = notification.color.map_or_else(|| String::from("0"), |c| c.to_string());
+    let font_size = notification.font_size.map(|size| format!("fontsize:{size} ")).unwrap_or_default();
     expect_ok(&send_command(
         format!(
-            "/notify {} {} {color} {message}",
-            icon as i8,
-            time.as_millis()
+            "/notify {} {} {color} {font_size}{}",
+            notification.icon as i8,
+            notification.time.as_millis(),
+            notification.message,
         )
         .as_bytes(),
     )?)
 }
+
+///[`send_notification`] with a color and the default font size.
+///
+///# Errors
+///See [`send_notification`].
+pub fn notify(icon: NotifyIcon, time: Duration, color: Color, message: &str) -> Result<()> {
+    send_notification(&Notification {
+        icon,
+        time,
+        color: Some(color),
+        font_size: None,
+        message: message.to_string(),
+    })
+}
+
+///Dismisses the `amount` oldest notifications, or all of them.
+///
+///# Errors
+///If Hyprland can't be reached or doesn't answer `ok`.
+pub fn dismiss_notify(amount: Option<u32>) -> Result<()> {
+    let amount = amount.map_or(-1, i64::from);
+    expect_ok(&send_command(format!("/dismissnotify {amount}").as_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        for (s, color) in [
+            ("#ff0000", Color::Rgb(255, 0, 0)),
+            ("#FF000080", Color::Rgba(255, 0, 0, 128)),
+            ("rgb(1, 2, 3)", Color::Rgb(1, 2, 3)),
+            ("rgba(1,2,3,255)", Color::Rgba(1, 2, 3, 255)),
+            ("rgba(1,2,3,1)", Color::Rgba(1, 2, 3, 1)),
+            ("rgb(0a0b0c)", Color::Rgb(10, 11, 12)),
+            (" rgba(0a0b0c0d) ", Color::Rgba(10, 11, 12, 13)),
+        ] {
+            assert_eq!(s.parse(), Ok(color), "{s}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_colors() {
+        for s in ["", "red", "#ff00", "#ff000", "#gg0000", "rgb(1,2)", "rgb(1,2,3,4)", "rgba(1,2,3)", "rgba(1,2,3,0.5)", "rgb(256,0,0)", "rgb(1,2,3"] {
+            assert!(s.parse::<Color>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn displays_what_hyprland_reads() {
+        assert_eq!(Color::Rgb(255, 0, 0).to_string(), "rgb(255,0,0)");
+        assert_eq!(Color::Rgba(1, 2, 3, 128).to_string(), "rgba(01020380)");
+        for color in [Color::Rgb(1, 2, 3), Color::Rgba(1, 2, 3, 4)] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+    }
+}
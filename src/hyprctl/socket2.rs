@@ -1,13 +1,13 @@
 use std::{
     io::{BufRead, BufReader, ErrorKind},
-    os::unix::net::UnixStream,
+    os::{fd::{AsRawFd, RawFd}, unix::net::UnixStream},
 };
 
 use anyhow::{bail, Result};
 
 use super::SOCKET2;
 
-type WindowAddress = u64;
+pub type WindowAddress = u64;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -189,6 +189,178 @@ pub enum Event {
     },
 }
 
+impl Event {
+    ///The name Hyprland uses for this event, e.g. `workspacev2`.
+    #[must_use]
+    pub fn kind(&self) -> &str {
+        match self {
+            Event::Workspace { .. } => "workspace",
+            Event::WorkspaceV2 { .. } => "workspacev2",
+            Event::FocusedMon { .. } => "focusedmon",
+            Event::FocusedMonV2 { .. } => "focusedmonv2",
+            Event::ActiveWindow { .. } => "activewindow",
+            Event::ActiveWindowV2 { .. } => "activewindowv2",
+            Event::Fullscreen { .. } => "fullscreen",
+            Event::MonitorRemoved { .. } => "monitorremoved",
+            Event::MonitorRemovedV2 { .. } => "monitorremovedv2",
+            Event::MonitorAdded { .. } => "monitoradded",
+            Event::MonitorAddedV2 { .. } => "monitoraddedv2",
+            Event::CreateWorkspace { .. } => "createworkspace",
+            Event::CreateWorkspaceV2 { .. } => "createworkspacev2",
+            Event::DestroyWorkspace { .. } => "destroyworkspace",
+            Event::DestroyWorkspaceV2 { .. } => "destroyworkspacev2",
+            Event::MoveWorkspace { .. } => "moveworkspace",
+            Event::MoveWorkspaceV2 { .. } => "moveworkspacev2",
+            Event::RenameWorkspace { .. } => "renameworkspace",
+            Event::ActiveSpecial { .. } => "activespecial",
+            Event::ActiveSpecialV2 { .. } => "activespecialv2",
+            Event::ActiveLayout { .. } => "activelayout",
+            Event::OpenWindow { .. } => "openwindow",
+            Event::CloseWindow { .. } => "closewindow",
+            Event::MoveWindow { .. } => "movewindow",
+            Event::MoveWindowV2 { .. } => "movewindowv2",
+            Event::OpenLayer { .. } => "openlayer",
+            Event::CloseLayer { .. } => "closelayer",
+            Event::Submap { .. } => "submap",
+            Event::ChangeFloatingMode { .. } => "changefloatingmode",
+            Event::Urgent { .. } => "urgent",
+            Event::ScreenCast { .. } => "screencast",
+            Event::WindowTitle { .. } => "windowtitle",
+            Event::WindowTitleV2 { .. } => "windowtitlev2",
+            Event::ToggleGroup { .. } => "togglegroup",
+            Event::MoveIntoGroup { .. } => "moveintogroup",
+            Event::MoveOutOfGroup { .. } => "moveoutofgroup",
+            Event::IgnoreGroupLock { .. } => "ignoregrouplock",
+            Event::LockGroups { .. } => "lockgroups",
+            Event::ConfigReloaded => "configreloaded",
+            Event::Pin { .. } => "pin",
+            Event::Minimized { .. } => "minimized",
+            Event::Bell { .. } => "bell",
+            Event::Custom { name, .. } => name,
+        }
+    }
+
+    ///The event's parameters as `(name, value)` pairs, named consistently across events (e.g.
+    ///`workspace_name`, `window_class`). Window addresses are formatted the way `hyprctl` expects.
+    #[must_use]
+    #[allow(clippy::too_many_lines)]
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let address = |address: &WindowAddress| format!("0x{address:x}");
+        let flag = |value: &bool| String::from(if *value { "1" } else { "0" });
+        match self {
+            Event::Workspace { name }
+            | Event::CreateWorkspace { name }
+            | Event::DestroyWorkspace { name } => vec![("workspace_name", name.clone())],
+            Event::WorkspaceV2 { id, name }
+            | Event::CreateWorkspaceV2 { id, name }
+            | Event::DestroyWorkspaceV2 { id, name }
+            | Event::RenameWorkspace { id, new_name: name } => vec![
+                ("workspace_id", id.to_string()),
+                ("workspace_name", name.clone()),
+            ],
+            Event::FocusedMon { workspace_name, monitor_name }
+            | Event::MoveWorkspace { workspace_name, monitor_name } => vec![
+                ("workspace_name", workspace_name.clone()),
+                ("monitor_name", monitor_name.clone()),
+            ],
+            Event::FocusedMonV2 { workspace_id, monitor_name } => vec![
+                ("workspace_id", workspace_id.to_string()),
+                ("monitor_name", monitor_name.clone()),
+            ],
+            Event::MoveWorkspaceV2 { workspace_id, workspace_name, monitor_name } => vec![
+                ("workspace_id", workspace_id.to_string()),
+                ("workspace_name", workspace_name.clone()),
+                ("monitor_name", monitor_name.clone()),
+            ],
+            Event::ActiveWindow { window } => window.iter().flat_map(|window| [
+                ("window_class", window.class.clone()),
+                ("window_title", window.title.clone()),
+            ]).collect(),
+            Event::ActiveWindowV2 { window_address } => {
+                window_address.iter().map(|a| ("window_address", address(a))).collect()
+            }
+            Event::Fullscreen { active } | Event::IgnoreGroupLock { active } => vec![("active", flag(active))],
+            Event::MonitorRemoved { name } | Event::MonitorAdded { name } => vec![("monitor_name", name.clone())],
+            Event::MonitorRemovedV2 { id, name, description }
+            | Event::MonitorAddedV2 { id, name, description } => vec![
+                ("monitor_id", id.to_string()),
+                ("monitor_name", name.clone()),
+                ("monitor_description", description.clone()),
+            ],
+            Event::ActiveSpecial { workspace_name, monitor_name } => {
+                let mut fields = vec![("monitor_name", monitor_name.clone())];
+                fields.extend(workspace_name.iter().map(|name| ("workspace_name", name.clone())));
+                fields
+            }
+            Event::ActiveSpecialV2 { workspace, monitor_name } => {
+                let mut fields = vec![("monitor_name", monitor_name.clone())];
+                if let Some(workspace) = workspace {
+                    fields.push(("workspace_id", workspace.id.to_string()));
+                    fields.push(("workspace_name", workspace.name.clone()));
+                }
+                fields
+            }
+            Event::ActiveLayout { keyboard_name, layout_name } => vec![
+                ("keyboard_name", keyboard_name.clone()),
+                ("layout_name", layout_name.clone()),
+            ],
+            Event::OpenWindow { window_address, workspace_name, window_class, window_title } => vec![
+                ("window_address", address(window_address)),
+                ("workspace_name", workspace_name.clone()),
+                ("window_class", window_class.clone()),
+                ("window_title", window_title.clone()),
+            ],
+            Event::CloseWindow { window_address }
+            | Event::Urgent { window_address }
+            | Event::WindowTitle { window_address }
+            | Event::MoveIntoGroup { window_address }
+            | Event::MoveOutOfGroup { window_address }
+            | Event::Bell { window_address } => vec![("window_address", address(window_address))],
+            Event::MoveWindow { window_address, workspace_name } => vec![
+                ("window_address", address(window_address)),
+                ("workspace_name", workspace_name.clone()),
+            ],
+            Event::MoveWindowV2 { window_address, workspace_id, workspace_name } => vec![
+                ("window_address", address(window_address)),
+                ("workspace_id", workspace_id.to_string()),
+                ("workspace_name", workspace_name.clone()),
+            ],
+            Event::OpenLayer { namespace } | Event::CloseLayer { namespace } => vec![("layer_namespace", namespace.clone())],
+            Event::Submap { name } => vec![("submap_name", name.clone().unwrap_or_default())],
+            Event::ChangeFloatingMode { window_address, floating } => vec![
+                ("window_address", address(window_address)),
+                ("floating", flag(floating)),
+            ],
+            Event::ScreenCast { active, owner } => vec![
+                ("active", flag(active)),
+                ("owner", String::from(match owner {
+                    ScreenCastOwner::Monitor => "monitor",
+                    ScreenCastOwner::Window => "window",
+                })),
+            ],
+            Event::WindowTitleV2 { window_address, window_title } => vec![
+                ("window_address", address(window_address)),
+                ("window_title", window_title.clone()),
+            ],
+            Event::ToggleGroup { exists, window_addresses } => vec![
+                ("exists", flag(exists)),
+                ("window_addresses", window_addresses.iter().map(address).collect::<Vec<_>>().join(",")),
+            ],
+            Event::LockGroups { locked } => vec![("locked", flag(locked))],
+            Event::ConfigReloaded => vec![],
+            Event::Pin { window_address, pinned } => vec![
+                ("window_address", address(window_address)),
+                ("pinned", flag(pinned)),
+            ],
+            Event::Minimized { window_address, state } => vec![
+                ("window_address", address(window_address)),
+                ("minimized", flag(state)),
+            ],
+            Event::Custom { params, .. } => vec![("params", params.join(","))],
+        }
+    }
+}
+
 macro_rules! params {
     ($in:ident => $a:ident) => {
 		let $a = $in;
@@ -521,6 +693,7 @@ fn read_event(line: &str) -> Result<Event> {
 #[derive(Debug)]
 pub struct Socket2 {
     stream: BufReader<UnixStream>,
+    closed: bool,
 }
 impl Socket2 {
     pub fn new() -> Result<Self> {
@@ -528,14 +701,37 @@ impl Socket2 {
         stream.set_nonblocking(true)?;
         Ok(Socket2 {
             stream: BufReader::new(stream),
+            closed: false,
         })
     }
+
+    ///Whether Hyprland closed the socket, which usually means it exited.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    ///Whether events were read from the socket but not yet returned, so waiting on the socket would
+    ///miss them.
+    #[must_use]
+    pub fn has_buffered(&self) -> bool {
+        !self.stream.buffer().is_empty()
+    }
+}
+impl AsRawFd for Socket2 {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.get_ref().as_raw_fd()
+    }
 }
 impl Iterator for Socket2 {
     type Item = Result<Event>;
     fn next(&mut self) -> Option<Self::Item> {
         let mut buf = String::new();
         match self.stream.read_line(&mut buf) {
+            Ok(0) => {
+                self.closed = true;
+                None
+            }
             Ok(_) => Some(read_event(buf.trim())),
             Err(e) if e.kind() == ErrorKind::WouldBlock => None,
             Err(e) => Some(Err(e.into())),
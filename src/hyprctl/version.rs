@@ -0,0 +1,23 @@
+use super::send_command;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[allow(unused)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Version {
+    pub branch: String,
+    pub commit: String,
+    ///Missing before Hyprland 0.42, where only `tag` is set.
+    pub version: String,
+    pub tag: String,
+    pub dirty: bool,
+}
+
+///What Hyprland reports about its build, like `hyprctl version`.
+///
+///# Errors
+///If Hyprland can't be reached or its answer can't be parsed.
+pub fn version() -> Result<Version> {
+    Ok(serde_json::from_str(&send_command(b"j/version")?)?)
+}
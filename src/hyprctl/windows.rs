@@ -0,0 +1,74 @@
+use super::{send_command, MonitorWorkspace, WindowAddress};
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+///`0x`-prefixed hex, as `hyprctl` prints addresses.
+fn address<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WindowAddress, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    u64::from_str_radix(text.trim_start_matches("0x"), 16).map_err(serde::de::Error::custom)
+}
+
+fn addresses<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<WindowAddress>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?.iter()
+        .map(|text| u64::from_str_radix(text.trim_start_matches("0x"), 16).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize_repr, Deserialize_repr, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FullscreenMode {
+    #[default]
+    None       = 0,
+    Maximized  = 1,
+    Fullscreen = 2,
+    Both       = 3,
+}
+
+///What Hyprland calls a client.
+#[allow(unused, clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Window {
+    #[serde(deserialize_with = "address")]
+    pub address: WindowAddress,
+    pub mapped: bool,
+    pub hidden: bool,
+    pub workspace: MonitorWorkspace,
+    pub floating: bool,
+    pub monitor: i32,
+    pub class: String,
+    pub title: String,
+    pub initial_class: String,
+    pub initial_title: String,
+    pub pid: i32,
+    pub xwayland: bool,
+    pub pinned: bool,
+    pub fullscreen: FullscreenMode,
+    ///Every window in its group, itself included; empty if it isn't in one.
+    #[serde(deserialize_with = "addresses")]
+    pub grouped: Vec<WindowAddress>,
+    #[serde(rename = "focusHistoryID")]
+    pub focus_history_id: i32,
+}
+
+///Every window Hyprland knows of, like `hyprctl clients`, including unmapped ones.
+///
+///# Errors
+///If Hyprland can't be reached or its answer can't be parsed.
+pub fn windows() -> Result<Vec<Window>> {
+    Ok(serde_json::from_str(&send_command(b"j/clients")?)?)
+}
+
+///`None` if no window has focus.
+///
+///# Errors
+///If Hyprland can't be reached or its answer can't be parsed.
+pub fn active_window() -> Result<Option<Window>> {
+    let reply = send_command(b"j/activewindow")?;
+    //Hyprland answers `{}` when nothing is focused.
+    if serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&reply).is_ok_and(|fields| fields.is_empty()) {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&reply)?))
+}
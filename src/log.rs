@@ -0,0 +1,240 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::{Display, Write},
+	io::Write as _,
+	os::unix::net::UnixStream,
+	sync::{LazyLock, Mutex, OnceLock},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, hyprctl};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+	Debug,
+	#[default]
+	Info,
+	Warn,
+	Error,
+} impl Level {
+	///Priority prefix understood by journald, see `sd-daemon(3)`.
+	fn syslog_prefix(self) -> &'static str {
+		match self {
+			Level::Debug => "<7>",
+			Level::Info => "<6>",
+			Level::Warn => "<4>",
+			Level::Error => "<3>",
+		}
+	}
+} impl Display for Level {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Level::Debug => "DEBUG",
+			Level::Info => "INFO",
+			Level::Warn => "WARN",
+			Level::Error => "ERROR",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+	///Increases by one per record, so followers can tell what they've missed.
+	pub seq: u64,
+	///Milliseconds since the Unix epoch.
+	pub time: u64,
+	pub level: Level,
+	///Module that wrote the record.
+	pub target: String,
+	///Name of the main loop step involved, if any.
+	pub step: Option<String>,
+	///Kind of Hyprland event being handled, if any.
+	pub event: Option<String>,
+	pub message: String,
+} impl Record {
+	///Everything but the timestamp.
+	fn body(&self) -> String {
+		let mut body = format!("{:5} {}", self.level, self.target);
+		if let Some(step) = &self.step {
+			let _ = write!(body, " step={step}");
+		}
+		if let Some(event) = &self.event {
+			let _ = write!(body, " event={event}");
+		}
+		let _ = write!(body, ": {}", self.message);
+		body
+	}
+} impl Display for Record {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		//UTC, which is good enough to line records up with each other.
+		let seconds = self.time / 1000;
+		write!(
+			f,
+			"{:02}:{:02}:{:02}.{:03} {}",
+			seconds / 3600 % 24, seconds / 60 % 60, seconds % 60, self.time % 1000,
+			self.body(),
+		)
+	}
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+	records: VecDeque<Record>,
+	next_seq: u64,
+}
+
+static BUFFER: LazyLock<Mutex<Buffer>> = LazyLock::new(Mutex::default);
+///Written a byte to whenever a record is kept.
+static WAKER: OnceLock<UnixStream> = OnceLock::new();
+
+///Makes every record kept from now on write to `waker`, so that a loop waiting on its other end
+///can pass the record on right away. `waker` should be non-blocking.
+pub fn wake_on_write(waker: UnixStream) {
+	let _ = WAKER.set(waker);
+}
+
+///Records at or above the configured level are printed to stderr and kept in the ring buffer.
+///Use the [`error!`](crate::error), [`warn!`](crate::warn), [`info!`](crate::info) and
+///[`debug!`](crate::debug) macros instead of calling this directly.
+pub fn write(level: Level, target: &str, step: Option<&str>, event: Option<&str>, message: String) {
+	let config = config::current();
+	if level < config.logging.level {
+		return;
+	}
+
+	let mut buffer = BUFFER.lock().unwrap();
+	let record = Record {
+		seq: buffer.next_seq,
+		time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| u64::try_from(t.as_millis()).unwrap_or(u64::MAX)),
+		level,
+		target: target.to_string(),
+		step: step.map(String::from),
+		event: event.map(String::from),
+		message,
+	};
+	buffer.next_seq += 1;
+
+	//journald already timestamps everything, and understands the priority prefix.
+	if std::env::var_os("JOURNAL_STREAM").is_some() {
+		eprintln!("{}{}", level.syslog_prefix(), record.body());
+	} else {
+		eprintln!("{record}");
+	}
+
+	buffer.records.push_back(record);
+	while buffer.records.len() > config.logging.buffer {
+		buffer.records.pop_front();
+	}
+	//A full pipe will wake the loop up just as well.
+	if let Some(mut waker) = WAKER.get() {
+		let _ = waker.write(&[0]);
+	}
+}
+
+///Buffered records at or above `level`, starting after `after` (a `seq`).
+pub fn records(level: Level, after: Option<u64>) -> Vec<Record> {
+	BUFFER.lock().unwrap().records.iter()
+		.filter(|r| r.level >= level && after.is_none_or(|after| r.seq > after))
+		.cloned()
+		.collect()
+}
+
+#[derive(Debug, Default)]
+struct NotificationLimiter {
+	shown: VecDeque<Instant>,
+	last_shown: HashMap<String, Instant>,
+}
+impl NotificationLimiter {
+	fn allow(&mut self, message: &str, config: &config::NotificationConfig) -> bool {
+		let now = Instant::now();
+		let dedup_window = Duration::from_millis(config.dedup_window_ms);
+		self.last_shown.retain(|_, shown| now.duration_since(*shown) < dedup_window);
+		while self.shown.front().is_some_and(|shown| now.duration_since(*shown) >= Duration::from_mins(1)) {
+			self.shown.pop_front();
+		}
+
+		if self.last_shown.contains_key(message) || self.shown.len() >= config.max_per_minute {
+			return false;
+		}
+		self.last_shown.insert(message.to_string(), now);
+		self.shown.push_back(now);
+		true
+	}
+}
+
+static NOTIFICATIONS: LazyLock<Mutex<NotificationLimiter>> = LazyLock::new(Mutex::default);
+
+///Logs an error and, unless an identical one was shown recently or too many have been shown
+///this minute, pops up a Hyprland notification. Use [`log_error!`](crate::log_error).
+pub fn error_with_notification(target: &str, step: Option<&str>, event: Option<&str>, error: &anyhow::Error) {
+	write(Level::Error, target, step, event, format!("{error:#}"));
+
+	let config = config::current();
+	let message = format!("HomeHelper Error: {error}");
+	if config.notifications.enabled && NOTIFICATIONS.lock().unwrap().allow(&message, &config.notifications) {
+		let _ = hyprctl::notify(
+			hyprctl::NotifyIcon::Error,
+			config.notifications.duration(),
+			config.notifications.color,
+			&message,
+		);
+	}
+}
+
+///Writes a log record, e.g. `log!(Level::Info, step = "submap", event = "submap"; "Opened {name}")`.
+///`step` and `event` are optional, but must come in that order.
+#[macro_export]
+macro_rules! log {
+	($level:expr, step = $step:expr, event = $event:expr; $($arg:tt)+) => {
+		$crate::log::write($level, module_path!(), Some($step), Some($event), format!($($arg)+))
+	};
+	($level:expr, step = $step:expr; $($arg:tt)+) => {
+		$crate::log::write($level, module_path!(), Some($step), None, format!($($arg)+))
+	};
+	($level:expr, event = $event:expr; $($arg:tt)+) => {
+		$crate::log::write($level, module_path!(), None, Some($event), format!($($arg)+))
+	};
+	($level:expr, $($arg:tt)+) => {
+		$crate::log::write($level, module_path!(), None, None, format!($($arg)+))
+	};
+}
+
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)+) => { $crate::log!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)+) => { $crate::log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)+) => { $crate::log!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)+) => { $crate::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+///Logs an `anyhow::Error` and shows it as a (rate-limited) notification.
+#[macro_export]
+macro_rules! log_error {
+	(step = $step:expr, event = $event:expr; $error:expr) => {
+		$crate::log::error_with_notification(module_path!(), Some($step), Some($event), $error)
+	};
+	(step = $step:expr; $error:expr) => {
+		$crate::log::error_with_notification(module_path!(), Some($step), None, $error)
+	};
+	(event = $event:expr; $error:expr) => {
+		$crate::log::error_with_notification(module_path!(), None, Some($event), $error)
+	};
+	($error:expr) => {
+		$crate::log::error_with_notification(module_path!(), None, None, $error)
+	};
+}
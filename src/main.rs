@@ -1,9 +1,11 @@
 #![warn(clippy::all, clippy::pedantic)]
 
-use anyhow::{Error, Result};
+use anyhow::Result;
 use clap::Parser;
 
+mod config;
 mod daemon;
+mod log;
 pub mod hyprctl; //pub silences an error in daemon::commands::prelude
 
 #[derive(Debug, Clone, clap::Parser)]
@@ -17,19 +19,9 @@ struct Cli {
 enum Command {
     Daemon(daemon::Arguments),
     Remote(daemon::remote::Arguments),
-}
-
-pub fn log_error(error: &Error) {
-	use hyprctl::{NotifyIcon,Color};
-	use std::time::Duration;
-
-	eprintln!("{error:#}");
-	let _ = hyprctl::notify(
-		NotifyIcon::Error,
-		Duration::from_secs(5),
-		Color::Rgb(255, 0, 0),
-		&format!("HomeHelper Error: {error}"),
-	);
+    Config(config::Arguments),
+    ///Write systemd user units for the daemon.
+    InstallService(daemon::systemd::Arguments),
 }
 
 fn main() -> Result<()> {
@@ -39,7 +31,16 @@ fn main() -> Result<()> {
             daemon::Daemon::launch(args)?;
         }
         Command::Remote(args) => {
-            daemon::remote::launch(args)?;
+            if let Err(e) = daemon::remote::launch(args) {
+                eprintln!("{e:#}");
+                std::process::exit(daemon::reply::exit_code(&e));
+            }
+        }
+        Command::Config(args) => {
+            config::launch(args)?;
+        }
+        Command::InstallService(args) => {
+            daemon::systemd::install_service(&args)?;
         }
     }
     Ok(())